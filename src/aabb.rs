@@ -0,0 +1,92 @@
+use crate::{Point3, ray::Ray};
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub minimum: Point3,
+    pub maximum: Point3,
+}
+
+impl Aabb {
+    pub fn new(minimum: Point3, maximum: Point3) -> Self {
+        Self { minimum, maximum }
+    }
+
+    pub fn hit(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+        for a in 0..3 {
+            let inv_d = 1.0 / ray.direction()[a];
+            let mut t0 = (self.minimum[a] - ray.origin()[a]) * inv_d;
+            let mut t1 = (self.maximum[a] - ray.origin()[a]) * inv_d;
+
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+
+            if t_max <= t_min {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Grows any side thinner than `delta` so flat objects (triangles, quads)
+    /// still get a box that rays can hit.
+    pub fn padded(&self, delta: f64) -> Aabb {
+        let mut minimum = [self.minimum.x(), self.minimum.y(), self.minimum.z()];
+        let mut maximum = [self.maximum.x(), self.maximum.y(), self.maximum.z()];
+
+        for a in 0..3 {
+            if maximum[a] - minimum[a] < delta {
+                minimum[a] -= delta / 2.0;
+                maximum[a] += delta / 2.0;
+            }
+        }
+
+        Aabb::new(
+            Point3::new(minimum[0], minimum[1], minimum[2]),
+            Point3::new(maximum[0], maximum[1], maximum[2]),
+        )
+    }
+
+    pub fn surrounding_box(box0: &Aabb, box1: &Aabb) -> Aabb {
+        let small = Point3::new(
+            box0.minimum.x().min(box1.minimum.x()),
+            box0.minimum.y().min(box1.minimum.y()),
+            box0.minimum.z().min(box1.minimum.z()),
+        );
+        let big = Point3::new(
+            box0.maximum.x().max(box1.maximum.x()),
+            box0.maximum.y().max(box1.maximum.y()),
+            box0.maximum.z().max(box1.maximum.z()),
+        );
+
+        Aabb::new(small, big)
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        (self.minimum + self.maximum) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.maximum - self.minimum;
+
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    /// Index of the axis (0 = x, 1 = y, 2 = z) along which the box is widest.
+    pub fn longest_axis(&self) -> usize {
+        let d = self.maximum - self.minimum;
+
+        if d.x() > d.y() && d.x() > d.z() {
+            0
+        } else if d.y() > d.z() {
+            1
+        } else {
+            2
+        }
+    }
+}
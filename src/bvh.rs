@@ -0,0 +1,251 @@
+use std::{error::Error, fmt, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    hittable::{Hit, HitRecord},
+    hittable_list::HittableList,
+    ray::Ray,
+    sampler::Sampler,
+};
+
+/// Why a hierarchy could not be built over a set of objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BvhError {
+    /// There were no objects.
+    Empty,
+    /// An object has no bounding box, such as an empty `HittableList`.
+    Unbounded,
+}
+
+impl fmt::Display for BvhError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BvhError::Empty => f.write_str("cannot build a BVH over an empty list"),
+            BvhError::Unbounded => f.write_str("cannot put an object without a bounding box in a BVH"),
+        }
+    }
+}
+
+impl Error for BvhError {}
+
+/// Bounding volume hierarchy node. Every node has two children, which are
+/// either further nodes or the objects themselves.
+pub struct BvhNode {
+    left: Arc<dyn Hit>,
+    right: Arc<dyn Hit>,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+    /// Builds a hierarchy over the objects of `list`, which must not be
+    /// empty and whose objects must all have a bounding box.
+    pub fn new(list: &HittableList) -> Result<Self, BvhError> {
+        Self::from_objects(list.hittables_vec.clone())
+    }
+
+    pub fn from_objects(objects: Vec<Arc<dyn Hit>>) -> Result<Self, BvhError> {
+        if objects.is_empty() {
+            return Err(BvhError::Empty);
+        }
+
+        let mut items = objects
+            .into_iter()
+            .map(|object| match object.bounding_box() {
+                Some(bbox) => Ok((object, bbox)),
+                None => Err(BvhError::Unbounded),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::build(&mut items))
+    }
+
+    fn build(items: &mut [(Arc<dyn Hit>, Aabb)]) -> Self {
+        let bbox = items[1..].iter()
+            .fold(items[0].1, |acc, (_, b)| Aabb::surrounding_box(&acc, b));
+
+        let (left, right) = match items.len() {
+            1 => (items[0].0.clone(), items[0].0.clone()),
+            2 => (items[0].0.clone(), items[1].0.clone()),
+            _ => {
+                let mid = Self::split(items);
+                let (l, r) = items.split_at_mut(mid);
+                (Self::subtree(l), Self::subtree(r))
+            }
+        };
+
+        Self { left, right, bbox }
+    }
+
+    fn subtree(items: &mut [(Arc<dyn Hit>, Aabb)]) -> Arc<dyn Hit> {
+        if items.len() == 1 {
+            items[0].0.clone()
+        } else {
+            Arc::new(Self::build(items))
+        }
+    }
+
+    /// Sorts `items` along the widest axis of their centroids and returns the
+    /// split index with the lowest surface area heuristic cost.
+    fn split(items: &mut [(Arc<dyn Hit>, Aabb)]) -> usize {
+        let first = items[0].1.centroid();
+        let centroid_bounds = items[1..].iter().fold(Aabb::new(first, first), |acc, (_, b)| {
+            let c = b.centroid();
+            Aabb::surrounding_box(&acc, &Aabb::new(c, c))
+        });
+        let axis = centroid_bounds.longest_axis();
+
+        items.sort_unstable_by(|(_, a), (_, b)| a.centroid()[axis].total_cmp(&b.centroid()[axis]));
+
+        let n = items.len();
+
+        // suffix_area[i] is the area of the box around items[i..].
+        let mut suffix_area = vec![0.0; n];
+        let mut acc = items[n - 1].1;
+        for i in (0..n).rev() {
+            acc = Aabb::surrounding_box(&acc, &items[i].1);
+            suffix_area[i] = acc.surface_area();
+        }
+
+        let mut best = (n / 2, f64::INFINITY);
+        let mut acc = items[0].1;
+        for i in 1..n {
+            acc = Aabb::surrounding_box(&acc, &items[i - 1].1);
+            let cost = i as f64 * acc.surface_area() + (n - i) as f64 * suffix_area[i];
+            if cost < best.1 {
+                best = (i, cost);
+            }
+        }
+
+        best.0
+    }
+}
+
+impl Hit for BvhNode {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        if !self.bbox.hit(ray, t_min, t_max) {
+            return None;
+        }
+
+        let hit_left = self.left.hit(ray, t_min, t_max, sampler);
+        if Arc::ptr_eq(&self.left, &self.right) {
+            return hit_left;
+        }
+
+        let closest_so_far = hit_left.as_ref().map_or(t_max, |rec| rec.t);
+
+        match self.right.hit(ray, t_min, closest_so_far, sampler) {
+            Some(hit_right) => Some(hit_right),
+            None => hit_left,
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Point3,
+        material::Lambertian,
+        quad::Quad,
+        sampler::{IndependentSampler, Sampler},
+        sphere::Sphere,
+        triangle::Triangle,
+        vec3::Vec3,
+        Color3,
+    };
+
+    fn random_scene(sampler: &mut dyn Sampler, count: usize) -> HittableList {
+        let material = Arc::new(Lambertian::new(Color3::new(0.5, 0.5, 0.5)));
+        let mut list = HittableList::new();
+
+        for i in 0..count {
+            let p = Vec3::random_vec3_in_range(sampler, -10.0, 10.0);
+            let object: Arc<dyn Hit> = match i % 3 {
+                0 => Arc::new(Sphere::new(p, sampler.random_in_range(0.1, 1.5), material.clone())),
+                1 => Arc::new(Quad::new(
+                    p,
+                    Vec3::random_vec3_in_range(sampler, -2.0, 2.0),
+                    Vec3::random_vec3_in_range(sampler, -2.0, 2.0),
+                    material.clone(),
+                )),
+                _ => Arc::new(Triangle::new(
+                    p,
+                    p + Vec3::random_vec3_in_range(sampler, -2.0, 2.0),
+                    p + Vec3::random_vec3_in_range(sampler, -2.0, 2.0),
+                    material.clone(),
+                )),
+            };
+            list.add(object);
+        }
+
+        list
+    }
+
+    #[test]
+    fn agrees_with_the_linear_scan() {
+        let mut sampler = IndependentSampler::new(7);
+
+        for count in [1, 2, 3, 10, 200] {
+            let list = random_scene(&mut sampler, count);
+            let bvh = BvhNode::new(&list).unwrap();
+            let mut hits = 0;
+
+            for _ in 0..2000 {
+                let origin: Point3 = Vec3::random_vec3_in_range(&mut sampler, -15.0, 15.0);
+                // Aim near the objects so that most rays hit something.
+                let target = Vec3::random_vec3_in_range(&mut sampler, -10.0, 10.0);
+                let ray = Ray::new(origin, target - origin);
+
+                let expected = list.hit(&ray, 0.001, f64::INFINITY, &mut sampler);
+                match (expected, bvh.hit(&ray, 0.001, f64::INFINITY, &mut sampler)) {
+                    (None, None) => {}
+                    (Some(expected), Some(actual)) => {
+                        hits += 1;
+                        assert_eq!(expected.t, actual.t);
+                        assert_eq!(
+                            [expected.normal.x(), expected.normal.y(), expected.normal.z()],
+                            [actual.normal.x(), actual.normal.y(), actual.normal.z()]
+                        );
+                        assert_eq!(expected.front_face, actual.front_face);
+                    }
+                    (expected, actual) => panic!(
+                        "linear scan hit: {}, BVH hit: {}", expected.is_some(), actual.is_some()
+                    ),
+                }
+            }
+            assert!(hits > 0);
+        }
+    }
+
+    #[test]
+    fn respects_the_ray_interval() {
+        let mut sampler = IndependentSampler::new(11);
+        let list = random_scene(&mut sampler, 50);
+        let bvh = BvhNode::new(&list).unwrap();
+
+        for _ in 0..500 {
+            let origin = Vec3::random_vec3_in_range(&mut sampler, -15.0, 15.0);
+            let ray = Ray::new(origin, Vec3::random_vec3_in_range(&mut sampler, -10.0, 10.0) - origin);
+            let (t_min, t_max) = (0.3, 0.9);
+
+            let expected = list.hit(&ray, t_min, t_max, &mut sampler).map(|rec| rec.t);
+            assert_eq!(expected, bvh.hit(&ray, t_min, t_max, &mut sampler).map(|rec| rec.t));
+        }
+    }
+
+    #[test]
+    fn rejects_empty_and_unbounded_input() {
+        assert!(matches!(BvhNode::from_objects(Vec::new()), Err(BvhError::Empty)));
+
+        let material = Arc::new(Lambertian::new(Color3::new(0.5, 0.5, 0.5)));
+        let objects: Vec<Arc<dyn Hit>> = vec![
+            Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, material)),
+            Arc::new(HittableList::new()),
+        ];
+        assert!(matches!(BvhNode::from_objects(objects), Err(BvhError::Unbounded)));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::Point3;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vec3::Vec3;
 
 pub struct Camera {
@@ -11,6 +12,9 @@ pub struct Camera {
     pub v: Vec3,
     pub w: Vec3,
     pub lens_radius: f64,
+    /// Shutter open and close times.
+    pub time0: f64,
+    pub time1: f64,
 }
 
 impl Camera {
@@ -51,20 +55,83 @@ impl Camera {
             v,
             w,
             lens_radius,
+            time0: 0.0,
+            time1: 0.0,
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
+    /// Keeps the shutter open from `time0` to `time1`, so every ray is sent
+    /// at a random time in between.
+    pub fn with_shutter(mut self, time0: f64, time1: f64) -> Self {
+        self.time0 = time0;
+        self.time1 = time1;
+        self
+    }
+
+    pub fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Ray {
         // let rd = Vec3::random_in_unit_disk().multiply_coef(self.lens_radius);
         // let offset = self.u.multiply_coef(rd.x()) + self.v.multiply_coef(rd.y());
-        let rd = Vec3::random_in_unit_disk() * self.lens_radius;
+        let rd = Vec3::random_in_unit_disk(sampler) * self.lens_radius;
         let offset = self.u * rd.x() + self.v * rd.y();
+        // The time dimension is drawn even with the shutter closed, so opening
+        // it does not shift the dimensions every later bounce reads.
+        let shutter = sampler.random();
+        let time = if self.time1 > self.time0 {
+            self.time0 + (self.time1 - self.time0) * shutter
+        } else {
+            self.time0
+        };
 
-        Ray::new(
+        Ray::with_time(
             self.origin + offset,
             // self.lower_left_corner + self.horizontal.multiply_coef(s)
             //     + self.vertical.multiply_coef(t) - self.origin - offset
-            self.lower_left_corner + self.horizontal*s + self.vertical*t - self.origin - offset
+            self.lower_left_corner + self.horizontal*s + self.vertical*t - self.origin - offset,
+            time
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::IndependentSampler;
+
+    fn camera() -> Camera {
+        Camera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+            0.5,
+            1.0,
         )
     }
+
+    #[test]
+    fn rays_use_the_same_dimensions_with_any_shutter() {
+        for (time0, time1) in [(0.0, 0.0), (0.0, 1.0), (2.0, 1.0)] {
+            let mut still = IndependentSampler::new(8);
+            let mut moving = IndependentSampler::new(8);
+
+            let a = camera().get_ray(0.3, 0.6, &mut still);
+            let b = camera().with_shutter(time0, time1).get_ray(0.3, 0.6, &mut moving);
+
+            assert_eq!([a.origin().x(), a.origin().y()], [b.origin().x(), b.origin().y()]);
+            assert_eq!(still.random(), moving.random(), "shutter {}..{}", time0, time1);
+        }
+    }
+
+    #[test]
+    fn times_fall_within_the_shutter_interval() {
+        let mut sampler = IndependentSampler::new(1);
+        let open = camera().with_shutter(1.0, 3.0);
+
+        for _ in 0..100 {
+            assert!((1.0..3.0).contains(&open.get_ray(0.5, 0.5, &mut sampler).time()));
+        }
+        assert_eq!(camera().with_shutter(2.0, 2.0).get_ray(0.5, 0.5, &mut sampler).time(), 2.0);
+        assert_eq!(camera().with_shutter(2.0, 1.0).get_ray(0.5, 0.5, &mut sampler).time(), 2.0);
+    }
 }
@@ -0,0 +1,339 @@
+//! Checkpoints of a progressive render: the weighted color sum, weight,
+//! sample count and luminance statistics of every pixel, from which a later
+//! run can keep adding samples.
+//!
+//! The format is little-endian: an 8-byte magic, the width and height, the
+//! seed, the number of passes and the samples per pixel they add up to, the
+//! sampling settings (sampler and filter kind as u8, filter radius as f64,
+//! adaptive minimum samples as u32 and threshold as f64, samples per pass and
+//! in total as u32; a threshold or pass size of 0 means the option is off),
+//! the integrator settings (maximum depth as i32, roulette depth as u32 with
+//! `u32::MAX` for off), the scene's content hash as u64 and its name as a
+//! u32 length and UTF-8 bytes. Then, for each pixel (top row first), come
+//! the three channel sums and the weight as f64, the sample count as u32,
+//! and the count (u32), mean and M2 (f64) of its luminance.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    Color3,
+    filter::{Filter, FilterKind},
+    image::{Image, Welford},
+    render::AdaptiveSampling,
+    sampler::SamplerKind,
+};
+
+const MAGIC: &[u8; 8] = b"RTCKPT01";
+/// Header up to the scene name, which is the only part of variable length.
+const HEADER_LEN: usize = 8 + 4 + 4 + 8 + 4 + 4 + SETTINGS_LEN + INTEGRATOR_LEN + 8 + 4;
+const SETTINGS_LEN: usize = 1 + 1 + 8 + 4 + 8 + 4 + 4;
+const INTEGRATOR_LEN: usize = 4 + 4;
+const PIXEL_LEN: usize = 4 * 8 + 4 + 4 + 2 * 8;
+
+const SAMPLERS: [SamplerKind; 4] = [
+    SamplerKind::Independent,
+    SamplerKind::Stratified,
+    SamplerKind::Halton,
+    SamplerKind::Sobol,
+];
+const FILTERS: [FilterKind; 4] = [FilterKind::Box, FilterKind::Tent, FilterKind::Gaussian, FilterKind::Mitchell];
+
+/// Settings that decide which samples a pass takes and how they are
+/// weighted. Passes rendered with different ones do not add up to a
+/// consistent image.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SamplingSettings {
+    pub sampler: SamplerKind,
+    pub filter: Filter,
+    pub adaptive: Option<AdaptiveSampling>,
+    /// Samples per pixel each pass adds, or `None` for a single pass.
+    pub pass_samples: Option<u32>,
+    /// Samples per pixel the render aims for, over which the stratified
+    /// sampler spreads its strata.
+    pub total_samples: u32,
+}
+
+/// Settings of the path tracer that change what each sample computes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntegratorSettings {
+    pub max_depth: i32,
+    pub roulette_depth: Option<u32>,
+}
+
+/// What a checkpoint was rendered from: a built-in scene's name or a scene
+/// file's path, and a hash of the file's contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SceneId {
+    pub name: String,
+    pub hash: u64,
+}
+
+impl SceneId {
+    /// Identifies the scene `name` described by `contents`, which is empty
+    /// for built-in scenes.
+    pub fn new(name: impl Into<String>, contents: &[u8]) -> Self {
+        // 64-bit FNV-1a: stable across builds, unlike the standard hasher.
+        let hash = contents.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+
+        Self { name: name.into(), hash }
+    }
+}
+
+pub struct Checkpoint {
+    pub image: Image,
+    /// Seed the passes were rendered with; resuming with another one would
+    /// repeat or mix up sample sequences.
+    pub seed: u64,
+    /// Passes accumulated so far, which is also the index of the next one.
+    pub passes: u32,
+    /// Samples per pixel the passes added up to.
+    pub samples_per_pixel: u32,
+    pub settings: SamplingSettings,
+    pub integrator: IntegratorSettings,
+    pub scene: SceneId,
+}
+
+impl Checkpoint {
+    pub fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let (width, height) = (self.image.width(), self.image.height());
+        let name = self.scene.name.as_bytes();
+        let name_len = u32::try_from(name.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "scene name is too long"))?;
+        let mut bytes = Vec::with_capacity(HEADER_LEN + name.len() + width as usize * height as usize * PIXEL_LEN);
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.passes.to_le_bytes());
+        bytes.extend_from_slice(&self.samples_per_pixel.to_le_bytes());
+
+        let settings = &self.settings;
+        let adaptive = settings.adaptive.map_or((0, 0.0), |adaptive| (adaptive.min_samples, adaptive.threshold));
+        bytes.push(SAMPLERS.iter().position(|&kind| kind == settings.sampler).unwrap() as u8);
+        bytes.push(FILTERS.iter().position(|&kind| kind == settings.filter.kind).unwrap() as u8);
+        bytes.extend_from_slice(&settings.filter.radius.to_le_bytes());
+        bytes.extend_from_slice(&adaptive.0.to_le_bytes());
+        bytes.extend_from_slice(&adaptive.1.to_le_bytes());
+        bytes.extend_from_slice(&settings.pass_samples.unwrap_or(0).to_le_bytes());
+        bytes.extend_from_slice(&settings.total_samples.to_le_bytes());
+
+        bytes.extend_from_slice(&self.integrator.max_depth.to_le_bytes());
+        bytes.extend_from_slice(&self.integrator.roulette_depth.unwrap_or(u32::MAX).to_le_bytes());
+
+        bytes.extend_from_slice(&self.scene.hash.to_le_bytes());
+        bytes.extend_from_slice(&name_len.to_le_bytes());
+        bytes.extend_from_slice(name);
+
+        for y in 0..height {
+            for x in 0..width {
+                let sum = self.image.pixel_sum(x, y);
+                for channel in [sum.x(), sum.y(), sum.z()] {
+                    bytes.extend_from_slice(&channel.to_le_bytes());
+                }
+                bytes.extend_from_slice(&self.image.weight(x, y).to_le_bytes());
+                bytes.extend_from_slice(&self.image.sample_count(x, y).to_le_bytes());
+
+                let stats = self.image.pixel_stats(x, y);
+                bytes.extend_from_slice(&stats.count.to_le_bytes());
+                bytes.extend_from_slice(&stats.mean.to_le_bytes());
+                bytes.extend_from_slice(&stats.m2.to_le_bytes());
+            }
+        }
+
+        out.write_all(&bytes)
+    }
+
+    pub fn read(data: &[u8]) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid checkpoint: {}", message));
+
+        if data.len() < HEADER_LEN || &data[..8] != MAGIC {
+            return Err(invalid("missing header"));
+        }
+        let u32_at = |pos: usize| u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+        let u64_at = |pos: usize| u64::from_le_bytes(data[pos..pos + 8].try_into().unwrap());
+        let f64_at = |pos: usize| f64::from_bits(u64_at(pos));
+
+        let width = u32_at(8);
+        let height = u32_at(12);
+        let seed = u64_at(16);
+        let passes = u32_at(24);
+        let samples_per_pixel = u32_at(28);
+
+        let sampler = *SAMPLERS.get(data[32] as usize).ok_or_else(|| invalid("unknown sampler"))?;
+        let filter_kind = *FILTERS.get(data[33] as usize).ok_or_else(|| invalid("unknown filter"))?;
+        let threshold = f64_at(46);
+        let settings = SamplingSettings {
+            sampler,
+            filter: Filter::new(filter_kind, f64_at(34)),
+            adaptive: (threshold > 0.0).then(|| AdaptiveSampling { min_samples: u32_at(42), threshold }),
+            pass_samples: Some(u32_at(54)).filter(|&samples| samples > 0),
+            total_samples: u32_at(58),
+        };
+        let integrator = IntegratorSettings {
+            max_depth: u32_at(62) as i32,
+            roulette_depth: Some(u32_at(66)).filter(|&depth| depth != u32::MAX),
+        };
+
+        let name_len = u32_at(78) as usize;
+        let name = data.get(HEADER_LEN..HEADER_LEN.saturating_add(name_len)).ok_or_else(|| invalid("truncated header"))?;
+        let name = String::from_utf8(name.to_vec()).map_err(|_| invalid("scene name is not UTF-8"))?;
+        let scene = SceneId { name, hash: u64_at(70) };
+
+        let pixels_start = HEADER_LEN + name_len;
+        let expected = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|n| n.checked_mul(PIXEL_LEN))
+            .and_then(|n| n.checked_add(pixels_start));
+        if expected != Some(data.len()) {
+            return Err(invalid("pixel data does not match the image size"));
+        }
+
+        let mut image = Image::new(width, height);
+        let mut pos = pixels_start;
+        for y in 0..height {
+            for x in 0..width {
+                let sum = Color3::new(f64_at(pos), f64_at(pos + 8), f64_at(pos + 16));
+                image.splat(x, y, sum, f64_at(pos + 24));
+                image.count_samples(x, y, u32_at(pos + 32));
+                image.set_pixel_stats(x, y, Welford {
+                    count: u32_at(pos + 36),
+                    mean: f64_at(pos + 40),
+                    m2: f64_at(pos + 48),
+                });
+                pos += PIXEL_LEN;
+            }
+        }
+
+        Ok(Self { image, seed, passes, samples_per_pixel, settings, integrator, scene })
+    }
+
+    /// Writes the checkpoint next to `path` and then moves it into place, so
+    /// a crash while saving leaves the previous checkpoint intact.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+
+        let mut out = BufWriter::new(File::create(&temp)?);
+        self.write(&mut out)?;
+        out.into_inner().map_err(|err| err.into_error())?.sync_all()?;
+
+        fs::rename(&temp, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(&fs::read(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint() -> Checkpoint {
+        let mut image = Image::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                let v = (y * 3 + x) as f64;
+                image.splat(x, y, Color3::new(v, v * 0.5, -v), 1.0 + v);
+                image.count_samples(x, y, x + 10 * y);
+                image.set_pixel_stats(x, y, Welford { count: x + y, mean: v / 7.0, m2: v * v });
+            }
+        }
+
+        Checkpoint {
+            image,
+            seed: 0x0123_4567_89ab_cdef,
+            passes: 5,
+            samples_per_pixel: 40,
+            settings: SamplingSettings {
+                sampler: SamplerKind::Sobol,
+                filter: Filter::new(FilterKind::Gaussian, 1.25),
+                adaptive: Some(AdaptiveSampling { min_samples: 0, threshold: 0.05 }),
+                pass_samples: Some(8),
+                total_samples: 64,
+            },
+            integrator: IntegratorSettings { max_depth: 12, roulette_depth: Some(0) },
+            scene: SceneId::new("scenes/three_spheres.toml", b"[camera]\nvfov = 20\n"),
+        }
+    }
+
+    fn bytes(checkpoint: &Checkpoint) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        checkpoint.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trips_through_write_and_read() {
+        let original = checkpoint();
+        let bytes = bytes(&original);
+        assert_eq!(bytes.len(), HEADER_LEN + "scenes/three_spheres.toml".len() + 6 * PIXEL_LEN);
+
+        let read = Checkpoint::read(&bytes).unwrap();
+        assert_eq!((read.seed, read.passes, read.samples_per_pixel), (original.seed, 5, 40));
+        assert_eq!(read.settings, original.settings);
+        assert_eq!(read.integrator, original.integrator);
+        assert_eq!(read.scene, original.scene);
+        for y in 0..2 {
+            for x in 0..3 {
+                let (a, b) = (original.image.pixel_sum(x, y), read.image.pixel_sum(x, y));
+                assert_eq!([a.x(), a.y(), a.z()], [b.x(), b.y(), b.z()]);
+                assert_eq!(original.image.weight(x, y), read.image.weight(x, y));
+                assert_eq!(original.image.sample_count(x, y), read.image.sample_count(x, y));
+                assert_eq!(original.image.pixel_stats(x, y), read.image.pixel_stats(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_default_settings() {
+        let mut original = checkpoint();
+        original.settings = SamplingSettings::default();
+        original.integrator.roulette_depth = None;
+        original.scene = SceneId::new("cornell-box", &[]);
+
+        let read = Checkpoint::read(&bytes(&original)).unwrap();
+        assert_eq!(read.settings, SamplingSettings::default());
+        assert_eq!(read.integrator, IntegratorSettings { max_depth: 12, roulette_depth: None });
+        assert_eq!(read.scene, original.scene);
+    }
+
+    #[test]
+    fn scene_hashes_are_fnv_1a() {
+        assert_eq!(SceneId::new("", b"").hash, 0xcbf2_9ce4_8422_2325);
+        assert_eq!(SceneId::new("", b"a").hash, 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(SceneId::new("", b"foobar").hash, 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn rejects_truncated_and_foreign_files() {
+        let bytes = bytes(&checkpoint());
+        let error = |data: &[u8]| Checkpoint::read(data).err().unwrap().to_string();
+
+        let name_len = "scenes/three_spheres.toml".len();
+        assert_eq!(error(&bytes[..HEADER_LEN - 1]), "invalid checkpoint: missing header");
+        assert_eq!(error(&bytes[..HEADER_LEN + name_len - 1]), "invalid checkpoint: truncated header");
+        for len in [HEADER_LEN + name_len, bytes.len() - 1] {
+            assert_eq!(error(&bytes[..len]), "invalid checkpoint: pixel data does not match the image size");
+        }
+
+        let mut foreign = bytes.clone();
+        foreign[..8].copy_from_slice(b"\x89PNG\r\n\x1a\n");
+        assert_eq!(error(&foreign), "invalid checkpoint: missing header");
+
+        let mut unknown = bytes.clone();
+        unknown[32] = 4;
+        assert_eq!(error(&unknown), "invalid checkpoint: unknown sampler");
+        unknown[32] = 0;
+        unknown[33] = 9;
+        assert_eq!(error(&unknown), "invalid checkpoint: unknown filter");
+    }
+}
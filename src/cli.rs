@@ -0,0 +1,447 @@
+use std::{fmt, path::PathBuf};
+
+use create_image::{
+    filter::{Filter, FilterKind},
+    image::ImageFormat,
+    render::{AdaptiveSampling, DEFAULT_ROULETTE_DEPTH},
+    sampler::SamplerKind,
+    scenes::RenderSettings,
+    tonemap::{OutputTransform, ToneMapOperator},
+};
+
+const DEFAULT_ASPECT_RATIO: f64 = 3.0 / 2.0;
+const DEFAULT_WIDTH: u32 = 1200;
+const DEFAULT_SAMPLES_PER_PIXEL: u32 = 500;
+const DEFAULT_MAX_DEPTH: i32 = 50;
+const DEFAULT_MIN_SAMPLES: u32 = 16;
+
+pub const USAGE: &str = "\
+Usage: create_image [OPTIONS] [OUTPUT]
+
+Renders a scene and writes it to OUTPUT (or a P3 PPM stream on stdout).
+
+Options:
+  -w, --width <PIXELS>        Image width [default: 1200]
+  -H, --height <PIXELS>       Image height [default: width / aspect ratio]
+  -a, --aspect-ratio <RATIO>  Aspect ratio as W:H or a number [default: 3:2]
+  -s, --spp, --max-spp <N>    Samples per pixel, or the most a pixel gets with
+                              --adaptive [default: 500]
+  -d, --max-depth <N>         Maximum ray bounces [default: 50]
+      --roulette <N|off>      Bounces before Russian roulette may end a path;
+                              'off' cuts paths at --max-depth only [default: 3]
+  -o, --output <FILE>         Output file; the format follows the extension
+  -f, --format <FORMAT>       ppm, p6, png, pfm, exr or exr-float
+  -j, --threads <N>           Render threads [default: all cores]
+      --seed <N>              Seed for sampling and random scenes [default: 0]
+      --sampler <NAME>        independent, stratified, halton or sobol
+                              [default: independent]
+      --filter <NAME>         Pixel filter: box, tent, gaussian or mitchell
+                              [default: box]
+      --filter-radius <PIXELS>
+                              Filter radius [default: 0.5 for box, 1 for tent,
+                              1.5 for gaussian, 2 for mitchell]
+      --adaptive <THRESHOLD>  Stop sampling a pixel once its 95% confidence
+                              interval is below THRESHOLD times its mean
+                              luminance (e.g. 0.05)
+      --min-spp <N>           Samples every pixel gets with --adaptive
+                              [default: 16]
+      --exposure <STOPS>      Brighten (or darken, if negative) 8-bit output
+                              by this many stops [default: 0]
+      --tonemap <NAME>        Tone curve for 8-bit output: clamp, reinhard,
+                              aces or hable [default: clamp]
+      --white-balance <KELVIN>
+                              Make light of this color temperature neutral in
+                              8-bit output [default: off]
+      --heatmap <FILE>        Also write the per-pixel sample counts as an image
+      --pass-spp <N>          Render in passes of N samples per pixel, writing
+                              a preview after each [default: one pass]
+      --preview <FILE>        Where previews go [default: the output file]
+      --checkpoint <FILE>     Save the accumulated samples after each pass and
+                              resume from this file if it exists; resuming
+                              needs the same scene, size, seed, spp, depth,
+                              roulette, sampler, filter, --adaptive and
+                              --pass-spp settings
+      --stats                 Print how the paths ended and their mean length
+      --scene <NAME|FILE>     Built-in scene (random, bouncing-spheres,
+                              three-spheres, checkered-spheres, perlin-spheres,
+                              simple-light, cornell-box, cornell-smoke)
+                              or a scene file to load [default: random]
+  -h, --help                  Print this help
+
+Short options take their value either separately or attached (-j 4 or -j4);
+long options take it separately or after '=' (--seed 7 or --seed=7).
+
+Values given in a scene file's [render] table replace the defaults above;
+flags on the command line always win.
+
+PFM and EXR output always holds the unprocessed linear radiance; exposure,
+tone mapping and white balance only apply to PPM and PNG.
+";
+
+#[derive(Debug)]
+pub struct Options {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub aspect_ratio: Option<f64>,
+    pub samples_per_pixel: Option<u32>,
+    pub max_depth: Option<i32>,
+    pub roulette_depth: Option<u32>,
+    pub stats: bool,
+    pub sampler: SamplerKind,
+    pub filter: Filter,
+    pub adaptive: Option<AdaptiveSampling>,
+    pub transform: OutputTransform,
+    pub heatmap: Option<PathBuf>,
+    pub pass_samples: Option<u32>,
+    pub preview: Option<PathBuf>,
+    pub checkpoint: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub format: ImageFormat,
+    pub threads: Option<usize>,
+    pub seed: u64,
+    pub scene: String,
+}
+
+/// Image size and sampling after merging the flags with a scene's settings.
+#[derive(Debug)]
+pub struct Resolved {
+    pub width: u32,
+    pub height: u32,
+    pub aspect_ratio: f64,
+    pub samples_per_pixel: u32,
+    pub max_depth: i32,
+}
+
+pub enum Command {
+    Help,
+    Render(Box<Options>),
+}
+
+#[derive(Debug)]
+pub struct CliError(String);
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn error<T>(message: impl Into<String>) -> Result<T, CliError> {
+    Err(CliError(message.into()))
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, CliError> {
+    match value.parse() {
+        Ok(n) => Ok(n),
+        Err(_) => error(format!("invalid value '{}' for {}", value, flag)),
+    }
+}
+
+fn parse_aspect_ratio(value: &str) -> Result<f64, CliError> {
+    let ratio = match value.split_once(':') {
+        Some((w, h)) => {
+            let w: f64 = parse_number("--aspect-ratio", w)?;
+            let h: f64 = parse_number("--aspect-ratio", h)?;
+            w / h
+        }
+        None => parse_number("--aspect-ratio", value)?,
+    };
+
+    if !ratio.is_finite() || ratio <= 0.0 {
+        return error(format!("aspect ratio '{}' must be positive", value));
+    }
+
+    Ok(ratio)
+}
+
+/// Whether `arg` starts with a short option that takes a value.
+fn is_short_with_value(arg: &str) -> bool {
+    ["-w", "-H", "-a", "-s", "-d", "-o", "-f", "-j"].iter().any(|short| arg.starts_with(short))
+}
+
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, CliError> {
+    let mut width = None;
+    let mut height = None;
+    let mut aspect_ratio = None;
+    let mut samples_per_pixel = None;
+    let mut max_depth = None;
+    let mut roulette_depth = Some(DEFAULT_ROULETTE_DEPTH);
+    let mut stats = false;
+    let mut sampler = SamplerKind::default();
+    let mut filter_kind = FilterKind::Box;
+    let mut filter_radius = None;
+    let mut threshold = None;
+    let mut min_samples = None;
+    let mut exposure = 0.0;
+    let mut operator = ToneMapOperator::default();
+    let mut white_balance = None;
+    let mut heatmap = None;
+    let mut pass_samples = None;
+    let mut preview = None;
+    let mut checkpoint = None;
+    let mut output = None;
+    let mut format = None;
+    let mut threads = None;
+    let mut seed = 0;
+    let mut scene = String::from("random");
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // Accept `--flag value`, `--flag=value`, `-f value` and `-fvalue`.
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ if is_short_with_value(&arg) && arg.len() > 2 => (arg[..2].to_string(), Some(arg[2..].to_string())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = || match inline_value.clone().or_else(|| args.next()) {
+            Some(value) => Ok(value),
+            None => error(format!("missing value for {}", flag)),
+        };
+
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-w" | "--width" => width = Some(parse_number::<u32>(&flag, &value()?)?),
+            "-H" | "--height" => height = Some(parse_number::<u32>(&flag, &value()?)?),
+            "-a" | "--aspect-ratio" => aspect_ratio = Some(parse_aspect_ratio(&value()?)?),
+            "-s" | "--spp" | "--max-spp" => samples_per_pixel = Some(parse_number(&flag, &value()?)?),
+            "-d" | "--max-depth" => max_depth = Some(parse_number(&flag, &value()?)?),
+            "--roulette" => {
+                let value = value()?;
+                roulette_depth = match value.as_str() {
+                    "off" => None,
+                    _ => Some(parse_number(&flag, &value)?),
+                };
+            }
+            "--stats" => stats = true,
+            "--sampler" => {
+                let name = value()?;
+                match SamplerKind::from_name(&name) {
+                    Some(kind) => sampler = kind,
+                    None => return error(format!("unknown sampler '{}'", name)),
+                }
+            }
+            "--filter" => {
+                let name = value()?;
+                match FilterKind::from_name(&name) {
+                    Some(kind) => filter_kind = kind,
+                    None => return error(format!("unknown filter '{}'", name)),
+                }
+            }
+            "--filter-radius" => filter_radius = Some(parse_number::<f64>(&flag, &value()?)?),
+            "--adaptive" => threshold = Some(parse_number::<f64>(&flag, &value()?)?),
+            "--min-spp" => min_samples = Some(parse_number::<u32>(&flag, &value()?)?),
+            "--exposure" => exposure = parse_number::<f64>(&flag, &value()?)?,
+            "--tonemap" => {
+                let name = value()?;
+                match ToneMapOperator::from_name(&name) {
+                    Some(op) => operator = op,
+                    None => return error(format!("unknown tone mapping operator '{}'", name)),
+                }
+            }
+            "--white-balance" => white_balance = Some(parse_number::<f64>(&flag, &value()?)?),
+            "--heatmap" => heatmap = Some(PathBuf::from(value()?)),
+            "--pass-spp" => pass_samples = Some(parse_number::<u32>(&flag, &value()?)?),
+            "--preview" => preview = Some(PathBuf::from(value()?)),
+            "--checkpoint" => checkpoint = Some(PathBuf::from(value()?)),
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            "-f" | "--format" => {
+                let name = value()?;
+                match ImageFormat::from_name(&name) {
+                    Some(f) => format = Some(f),
+                    None => return error(format!("unknown format '{}'", name)),
+                }
+            }
+            "-j" | "--threads" => threads = Some(parse_number::<usize>(&flag, &value()?)?),
+            "--seed" => seed = parse_number(&flag, &value()?)?,
+            "--scene" => scene = value()?,
+            _ if flag.starts_with('-') && flag.len() > 1 => {
+                return error(format!("unknown option '{}'", flag));
+            }
+            _ => {
+                if output.is_some() {
+                    return error(format!("unexpected argument '{}'", arg));
+                }
+                output = Some(PathBuf::from(arg));
+            }
+        }
+    }
+
+    if width == Some(0) {
+        return error("width must be at least 1");
+    }
+    if height == Some(0) {
+        return error("height must be at least 1");
+    }
+    if samples_per_pixel == Some(0) {
+        return error("samples per pixel must be at least 1");
+    }
+    if matches!(max_depth, Some(d) if d < 1) {
+        return error("max depth must be at least 1");
+    }
+    if matches!(filter_radius, Some(r) if !(r > 0.0 && r.is_finite())) {
+        return error("the filter radius must be a positive number");
+    }
+    let filter = Filter::new(filter_kind, filter_radius.unwrap_or(filter_kind.default_radius()));
+    if matches!(threshold, Some(t) if !(t > 0.0 && t.is_finite())) {
+        return error("the adaptive threshold must be a positive number");
+    }
+    if threshold.is_none() && min_samples.is_some() {
+        return error("--min-spp needs --adaptive");
+    }
+    let adaptive = threshold.map(|threshold| AdaptiveSampling {
+        min_samples: min_samples.unwrap_or(DEFAULT_MIN_SAMPLES),
+        threshold,
+    });
+    if !exposure.is_finite() {
+        return error("the exposure must be a finite number");
+    }
+    if matches!(white_balance, Some(t) if !(t > 0.0 && t.is_finite())) {
+        return error("the white balance temperature must be a positive number");
+    }
+    let transform = OutputTransform::new(exposure, operator, white_balance);
+    if let Some(path) = &heatmap {
+        if ImageFormat::from_path(path).is_none() {
+            return error(format!("cannot infer the format of heatmap '{}'", path.display()));
+        }
+    }
+    if pass_samples == Some(0) {
+        return error("samples per pass must be at least 1");
+    }
+    if let Some(path) = &preview {
+        if ImageFormat::from_path(path).is_none() {
+            return error(format!("cannot infer the format of preview '{}'", path.display()));
+        }
+    }
+    if threads == Some(0) {
+        return error("thread count must be at least 1");
+    }
+    if width.is_some() && height.is_some() && aspect_ratio.is_some() {
+        return error("--width, --height and --aspect-ratio cannot all be given");
+    }
+
+    let format = match (format, &output) {
+        (Some(format), _) => format,
+        (None, Some(path)) => match ImageFormat::from_path(path) {
+            Some(format) => format,
+            None => {
+                return error(format!(
+                    "cannot infer the format of '{}'; use --format",
+                    path.display()
+                ));
+            }
+        },
+        (None, None) => ImageFormat::PpmAscii,
+    };
+
+    Ok(Command::Render(Box::new(Options {
+        width,
+        height,
+        aspect_ratio,
+        samples_per_pixel,
+        max_depth,
+        roulette_depth,
+        stats,
+        sampler,
+        filter,
+        adaptive,
+        transform,
+        heatmap,
+        pass_samples,
+        preview,
+        checkpoint,
+        output,
+        format,
+        threads,
+        seed,
+        scene,
+    })))
+}
+
+impl Options {
+    /// Fills in whatever the command line left open from the scene's
+    /// settings, then from the built-in defaults.
+    pub fn resolve(&self, render: &RenderSettings) -> Result<Resolved, CliError> {
+        // Flags override the scene file as a group: giving --width alone
+        // should not pair it with a height from the file.
+        let size_from_flags = self.width.is_some() || self.height.is_some() || self.aspect_ratio.is_some();
+        let (width, height, ratio) = if size_from_flags {
+            (self.width, self.height, self.aspect_ratio)
+        } else {
+            (render.width, render.height, render.aspect_ratio)
+        };
+
+        // Two of width, height and aspect ratio determine the third.
+        let (width, height, aspect_ratio) = match (width, height, ratio) {
+            (Some(w), Some(h), None) => (w, h, w as f64 / h as f64),
+            (Some(w), Some(h), Some(_)) => {
+                return error(format!(
+                    "the scene gives width {}, height {} and an aspect ratio; drop one of them", w, h
+                ));
+            }
+            (None, Some(h), ratio) => {
+                let ratio = ratio.unwrap_or(DEFAULT_ASPECT_RATIO);
+                ((h as f64 * ratio) as u32, h, ratio)
+            }
+            (w, None, ratio) => {
+                let w = w.unwrap_or(DEFAULT_WIDTH);
+                let ratio = ratio.unwrap_or(DEFAULT_ASPECT_RATIO);
+                (w, (w as f64 / ratio) as u32, ratio)
+            }
+        };
+
+        if width == 0 || height == 0 {
+            return error(format!(
+                "aspect ratio {:.4} gives a {}x{} image; increase the resolution",
+                aspect_ratio, width, height
+            ));
+        }
+
+        Ok(Resolved {
+            width,
+            height,
+            aspect_ratio,
+            samples_per_pixel: self.samples_per_pixel
+                .or(render.samples_per_pixel)
+                .unwrap_or(DEFAULT_SAMPLES_PER_PIXEL),
+            max_depth: self.max_depth.or(render.max_depth).unwrap_or(DEFAULT_MAX_DEPTH),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_options(args: &[&str]) -> Result<Options, CliError> {
+        match parse(args.iter().map(|arg| arg.to_string()))? {
+            Command::Render(options) => Ok(*options),
+            Command::Help => panic!("unexpected help"),
+        }
+    }
+
+    #[test]
+    fn short_options_take_attached_values() {
+        let options = parse_options(&["-j1", "-w320", "-s8", "-oout.png"]).unwrap();
+
+        assert_eq!(options.threads, Some(1));
+        assert_eq!(options.width, Some(320));
+        assert_eq!(options.samples_per_pixel, Some(8));
+        assert_eq!(options.output, Some(PathBuf::from("out.png")));
+        assert_eq!(options.format, ImageFormat::Png);
+    }
+
+    #[test]
+    fn separated_and_equals_forms_agree() {
+        let separated = parse_options(&["-j", "2", "--seed", "7", "-o", "out.pfm"]).unwrap();
+        let joined = parse_options(&["-j2", "--seed=7", "--output=out.pfm"]).unwrap();
+
+        assert_eq!((separated.threads, separated.seed), (joined.threads, joined.seed));
+        assert_eq!(separated.output, joined.output);
+    }
+
+    #[test]
+    fn unknown_short_options_are_still_rejected() {
+        assert_eq!(parse_options(&["-x1"]).unwrap_err().to_string(), "unknown option '-x1'");
+        assert_eq!(parse_options(&["-j0"]).unwrap_err().to_string(), "thread count must be at least 1");
+    }
+}
@@ -1,18 +1,62 @@
 use crate::Color3;
-use crate::rtweekend;
-
-pub fn write_color(pixel_color: Color3, samples_per_pixel: u32) {
-    // Divide the color by the number of samples and gamma-correct for gamma=2.0.
-    let scale = 1.0 / samples_per_pixel as f64;
-    let r = (pixel_color.x() * scale).sqrt();
-    let g = (pixel_color.y() * scale).sqrt();
-    let b = (pixel_color.z() * scale).sqrt();
-
-    let color = format!("{} {} {}",
-        (256.0 * rtweekend::clamp(r, 0.0, 0.999)) as i32,
-        (256.0 * rtweekend::clamp(g, 0.0, 0.999)) as i32,
-        (256.0 * rtweekend::clamp(b, 0.0, 0.999)) as i32,
-    );
-
-    println!("{}", color);
+
+/// Relative luminance of a linear color, with the Rec. 709 weights.
+pub fn luminance(color: Color3) -> f64 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+/// The sRGB transfer function: encodes a linear value in `[0, 1]`.
+pub fn linear_to_srgb(linear: f64) -> f64 {
+    if linear <= 0.0031308 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of [`linear_to_srgb`].
+pub fn srgb_to_linear(encoded: f64) -> f64 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_round_trips() {
+        for i in 0..=1000 {
+            let linear = i as f64 / 1000.0;
+            assert!((srgb_to_linear(linear_to_srgb(linear)) - linear).abs() < 1e-12, "{}", linear);
+
+            let encoded = i as f64 / 1000.0;
+            assert!((linear_to_srgb(srgb_to_linear(encoded)) - encoded).abs() < 1e-12, "{}", encoded);
+        }
+    }
+
+    #[test]
+    fn srgb_known_values() {
+        assert_eq!(linear_to_srgb(0.0), 0.0);
+        assert!((linear_to_srgb(1.0) - 1.0).abs() < 1e-15);
+        assert!((linear_to_srgb(0.5) - 0.735_356_983_052_449).abs() < 1e-12);
+        assert!((linear_to_srgb(0.001) - 0.012_92).abs() < 1e-15);
+    }
+
+    #[test]
+    fn srgb_pieces_meet_at_the_breakpoint() {
+        // The linear segment ends at 0.0031308 (0.04045 encoded), where the
+        // power curve takes over without a visible jump.
+        let below = linear_to_srgb(0.0031308);
+        let above = linear_to_srgb(0.0031308 + 1e-12);
+        assert_eq!(below, 12.92 * 0.0031308);
+        assert!((above - below).abs() < 1e-7, "{} vs {}", below, above);
+        assert!((below - 0.04045).abs() < 1e-5);
+
+        assert_eq!(srgb_to_linear(0.04045), 0.04045 / 12.92);
+        assert!((srgb_to_linear(0.04045 + 1e-12) - srgb_to_linear(0.04045)).abs() < 1e-7);
+    }
 }
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use crate::{
+    Color3,
+    aabb::Aabb,
+    hittable::{Hit, HitRecord},
+    material::{Isotropic, Material},
+    ray::Ray,
+    sampler::Sampler,
+    vec3::Vec3,
+};
+
+/// Volume of constant density filling a closed boundary shape, such as smoke
+/// or fog.
+///
+/// A ray travelling through the volume scatters after an exponentially
+/// distributed distance; rays that make it through are unaffected.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hit>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Arc<dyn Hit>, density: f64, phase_function: Arc<dyn Material>) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+        }
+    }
+
+    /// Medium that scatters uniformly in all directions with the given albedo.
+    pub fn from_color(boundary: Arc<dyn Hit>, density: f64, albedo: Color3) -> Self {
+        Self::new(boundary, density, Arc::new(Isotropic::new(albedo)))
+    }
+}
+
+impl Hit for ConstantMedium {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        // Find where the ray enters and leaves the boundary, even if it
+        // starts inside it. Assumes the boundary is convex.
+        let enter = self.boundary.hit(ray, f64::NEG_INFINITY, f64::INFINITY, sampler)?;
+        let exit = self.boundary.hit(ray, enter.t + 0.0001, f64::INFINITY, sampler)?;
+
+        let t_enter = enter.t.max(t_min).max(0.0);
+        let t_exit = exit.t.min(t_max);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let ray_length = ray.direction().length();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+
+        // The free-flight distance is the pixel sampler's next dimension.
+        // 1 - x keeps the logarithm finite.
+        let hit_distance = self.neg_inv_density * (1.0 - sampler.random()).ln();
+
+        if hit_distance > distance_inside_boundary {
+            return None;
+        }
+
+        let mut hit_record = HitRecord::new();
+
+        hit_record.t = t_enter + hit_distance / ray_length;
+        hit_record.p = ray.at(hit_record.t);
+        // Arbitrary: the phase function ignores the normal.
+        hit_record.normal = Vec3::new(1.0, 0.0, 0.0);
+        hit_record.front_face = true;
+        hit_record.material = Arc::clone(&self.phase_function);
+
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Point3, sampler::IndependentSampler, sphere::Sphere};
+
+    fn fog(density: f64) -> ConstantMedium {
+        let boundary = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Arc::new(Isotropic::new(Color3::new(1.0, 1.0, 1.0))));
+        ConstantMedium::from_color(Arc::new(boundary), density, Color3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn identical_rays_scatter_at_sampled_depths() {
+        let medium = fog(0.5);
+        let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut sampler = IndependentSampler::new(3);
+
+        let trials = 20_000;
+        let mut depths = Vec::new();
+        for _ in 0..trials {
+            if let Some(rec) = medium.hit(&ray, 0.001, f64::INFINITY, &mut sampler) {
+                assert!((4.0..=6.0).contains(&rec.t));
+                depths.push(rec.t);
+            }
+        }
+
+        depths.sort_by(f64::total_cmp);
+        depths.dedup();
+        assert!(depths.len() > trials / 2);
+
+        // Two units of fog at density 0.5 let exp(-1) of the rays through.
+        let passed = (trials - depths.len()) as f64 / trials as f64;
+        assert!((passed - (-1.0f64).exp()).abs() < 0.02, "{}", passed);
+    }
+
+    #[test]
+    fn depths_follow_the_sampler() {
+        let medium = fog(0.5);
+        let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let depths = |seed| {
+            let mut sampler = IndependentSampler::new(seed);
+            (0..16).map(|_| medium.hit(&ray, 0.001, f64::INFINITY, &mut sampler).map(|rec| rec.t)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(depths(1), depths(1));
+        assert_ne!(depths(1), depths(2));
+    }
+}
@@ -0,0 +1,249 @@
+//! Uncompressed scanline OpenEXR encoder for linear HDR images.
+
+use std::io::{self, Write};
+
+const MAGIC: [u8; 4] = [0x76, 0x2F, 0x31, 0x01];
+
+/// Storage type of the R, G and B channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExrPixelType {
+    Half,
+    Float,
+}
+
+impl ExrPixelType {
+    fn id(self) -> i32 {
+        match self {
+            ExrPixelType::Half => 1,
+            ExrPixelType::Float => 2,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ExrPixelType::Half => 2,
+            ExrPixelType::Float => 4,
+        }
+    }
+}
+
+/// Converts to IEEE 754 binary16, rounding to nearest even. Values beyond
+/// the half range become infinity.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xFF) as i32;
+    let mant = bits & 0x7F_FFFF;
+
+    if exp == 0xFF {
+        // Infinity stays infinity, NaN stays a (quiet) NaN.
+        return sign | 0x7C00 | if mant != 0 { 0x200 } else { 0 };
+    }
+
+    let half_exp = exp - 127 + 15;
+
+    if half_exp >= 0x1F {
+        return sign | 0x7C00;
+    }
+
+    if half_exp <= 0 {
+        // Subnormal half (or zero): shift the implicit-one mantissa into place.
+        if half_exp < -10 {
+            return sign;
+        }
+        let m = mant | 0x80_0000;
+        let shift = (14 - half_exp) as u32;
+        let mut half_mant = m >> shift;
+        let rem = m & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        if rem > halfway || (rem == halfway && half_mant & 1 == 1) {
+            half_mant += 1;
+        }
+        return sign | half_mant as u16;
+    }
+
+    let mut half = ((half_exp as u32) << 10) | (mant >> 13);
+    let rem = mant & 0x1FFF;
+    // A carry out of the mantissa correctly bumps the exponent (up to infinity).
+    if rem > 0x1000 || (rem == 0x1000 && half & 1 == 1) {
+        half += 1;
+    }
+
+    sign | half as u16
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+fn box2i(xmax: i32, ymax: i32) -> Vec<u8> {
+    [0, 0, xmax, ymax].iter().flat_map(|v: &i32| v.to_le_bytes()).collect()
+}
+
+/// Writes `rgb` (row-major, top row first) as a single-part scanline EXR
+/// without compression.
+pub fn write_exr<W: Write>(
+        out: &mut W,
+        width: u32,
+        height: u32,
+        rgb: &[[f32; 3]],
+        pixel_type: ExrPixelType
+    ) -> io::Result<()> {
+    let w = width as usize;
+    assert_eq!(rgb.len(), w * height as usize, "pixel buffer does not match image size");
+
+    // Channels must be listed (and stored) in alphabetical order.
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&pixel_type.id().to_le_bytes());
+        channels.extend_from_slice(&[0, 0, 0, 0]); // pLinear + reserved
+        channels.extend_from_slice(&1i32.to_le_bytes()); // xSampling
+        channels.extend_from_slice(&1i32.to_le_bytes()); // ySampling
+    }
+    channels.push(0);
+
+    let window = box2i(width as i32 - 1, height as i32 - 1);
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&2u32.to_le_bytes()); // version 2, single-part scanline
+    write_attribute(&mut header, "channels", "chlist", &channels);
+    write_attribute(&mut header, "compression", "compression", &[0]);
+    write_attribute(&mut header, "dataWindow", "box2i", &window);
+    write_attribute(&mut header, "displayWindow", "box2i", &window);
+    write_attribute(&mut header, "lineOrder", "lineOrder", &[0]); // INCREASING_Y
+    write_attribute(&mut header, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(&mut header, "screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    header.push(0);
+
+    // One chunk per scanline: y, byte count, then each channel's row.
+    let line_bytes = w * 3 * pixel_type.size();
+    let chunk_size = 8 + line_bytes;
+    let table_start = header.len() + height as usize * 8;
+
+    let mut body = Vec::with_capacity(height as usize * (8 + chunk_size));
+    for y in 0..height as usize {
+        body.extend_from_slice(&((table_start + y * chunk_size) as u64).to_le_bytes());
+    }
+
+    for (y, row) in rgb.chunks(w.max(1)).enumerate().take(height as usize) {
+        body.extend_from_slice(&(y as i32).to_le_bytes());
+        body.extend_from_slice(&(line_bytes as i32).to_le_bytes());
+
+        for channel in [2, 1, 0] {
+            for pixel in row {
+                match pixel_type {
+                    ExrPixelType::Half => {
+                        body.extend_from_slice(&f32_to_f16(pixel[channel]).to_le_bytes())
+                    }
+                    ExrPixelType::Float => body.extend_from_slice(&pixel[channel].to_le_bytes()),
+                }
+            }
+        }
+    }
+
+    out.write_all(&header)?;
+    out.write_all(&body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f16_normal_values() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(1.0), 0x3C00);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+        assert_eq!(f32_to_f16(-2.0), 0xC000);
+        assert_eq!(f32_to_f16(65504.0), 0x7BFF);
+        assert_eq!(f32_to_f16(2f32.powi(-14)), 0x0400);
+    }
+
+    #[test]
+    fn f16_subnormals() {
+        assert_eq!(f32_to_f16(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_f16(1023.0 * 2f32.powi(-24)), 0x03FF);
+        // Halfway cases go to the even neighbour.
+        assert_eq!(f32_to_f16(2f32.powi(-25)), 0x0000);
+        assert_eq!(f32_to_f16(3.0 * 2f32.powi(-25)), 0x0002);
+        assert_eq!(f32_to_f16(2f32.powi(-30)), 0x0000);
+        assert_eq!(f32_to_f16(-(2f32.powi(-24))), 0x8001);
+    }
+
+    #[test]
+    fn f16_overflows_to_infinity() {
+        // 65520 is halfway to the next (unrepresentable) step, so it rounds up.
+        assert_eq!(f32_to_f16(65519.0), 0x7BFF);
+        assert_eq!(f32_to_f16(65520.0), 0x7C00);
+        assert_eq!(f32_to_f16(1.0e6), 0x7C00);
+        assert_eq!(f32_to_f16(-1.0e6), 0xFC00);
+        assert_eq!(f32_to_f16(f32::INFINITY), 0x7C00);
+        assert_eq!(f32_to_f16(f32::NAN) & 0x7E00, 0x7E00);
+    }
+
+    #[test]
+    fn f16_rounds_to_nearest_even() {
+        let ulp = 2f32.powi(-10);
+
+        assert_eq!(f32_to_f16(1.0 + ulp / 2.0), 0x3C00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 * ulp / 2.0), 0x3C02);
+        assert_eq!(f32_to_f16(1.0 + ulp / 2.0 + 2f32.powi(-20)), 0x3C01);
+        assert_eq!(f32_to_f16(1.0 + ulp / 2.0 - 2f32.powi(-20)), 0x3C00);
+    }
+
+    /// Length of the header up to and including its terminating null byte.
+    fn header_len(data: &[u8]) -> usize {
+        let mut pos = 8;
+        loop {
+            let name_end = pos + data[pos..].iter().position(|&b| b == 0).unwrap();
+            if name_end == pos {
+                return pos + 1;
+            }
+            let kind_end = name_end + 1 + data[name_end + 1..].iter().position(|&b| b == 0).unwrap();
+            let size = i32::from_le_bytes(data[kind_end + 1..kind_end + 5].try_into().unwrap());
+            pos = kind_end + 5 + size as usize;
+        }
+    }
+
+    #[test]
+    fn offset_table_points_at_each_scanline() {
+        let (width, height) = (3u32, 4u32);
+        let rgb: Vec<[f32; 3]> = (0..width * height).map(|i| [i as f32, 0.5, -1.0]).collect();
+
+        for pixel_type in [ExrPixelType::Half, ExrPixelType::Float] {
+            let mut out = Vec::new();
+            write_exr(&mut out, width, height, &rgb, pixel_type).unwrap();
+            assert_eq!(&out[..4], &MAGIC);
+
+            let table = header_len(&out);
+            let line_bytes = width as usize * 3 * pixel_type.size();
+            for y in 0..height as usize {
+                let offset = u64::from_le_bytes(out[table + y * 8..table + y * 8 + 8].try_into().unwrap()) as usize;
+                assert_eq!(offset, table + height as usize * 8 + y * (8 + line_bytes));
+                assert_eq!(i32::from_le_bytes(out[offset..offset + 4].try_into().unwrap()), y as i32);
+                assert_eq!(i32::from_le_bytes(out[offset + 4..offset + 8].try_into().unwrap()), line_bytes as i32);
+            }
+            assert_eq!(out.len(), table + height as usize * (8 + 8 + line_bytes));
+
+            // Scanline 1 holds a row of B, then G, then R values; the first
+            // pixel's R is its index, 3.
+            if pixel_type == ExrPixelType::Float {
+                let first = table + height as usize * 8 + (8 + line_bytes) + 8;
+                let value = |pos: usize| f32::from_le_bytes(out[pos..pos + 4].try_into().unwrap());
+                assert_eq!(value(first), -1.0);
+                assert_eq!(value(first + 3 * 4), 0.5);
+                assert_eq!(value(first + 6 * 4), 3.0);
+            }
+        }
+    }
+}
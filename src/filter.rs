@@ -0,0 +1,195 @@
+/// Shapes of pixel reconstruction filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKind {
+    Box,
+    Tent,
+    Gaussian,
+    /// Mitchell-Netravali with B = C = 1/3.
+    Mitchell,
+}
+
+impl FilterKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "box" => Some(FilterKind::Box),
+            "tent" | "triangle" => Some(FilterKind::Tent),
+            "gaussian" => Some(FilterKind::Gaussian),
+            "mitchell" => Some(FilterKind::Mitchell),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FilterKind::Box => "box",
+            FilterKind::Tent => "tent",
+            FilterKind::Gaussian => "gaussian",
+            FilterKind::Mitchell => "mitchell",
+        }
+    }
+
+    /// Radius the filter is usually used with, in pixels.
+    pub fn default_radius(self) -> f64 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+        }
+    }
+}
+
+/// Weights a sample's contribution to the pixels around it by its distance
+/// from their centers, in pixels. The filter is separable and zero beyond
+/// `radius` on either axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Filter {
+    pub kind: FilterKind,
+    pub radius: f64,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, radius: f64) -> Self {
+        Self { kind, radius }
+    }
+
+    pub fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    fn evaluate_1d(&self, d: f64) -> f64 {
+        let r = self.radius;
+
+        match self.kind {
+            // Half-open, so a sample on the edge between two pixels only
+            // counts for one of them.
+            FilterKind::Box => if (-r..r).contains(&d) { 1.0 } else { 0.0 },
+            FilterKind::Tent => (1.0 - d.abs() / r).max(0.0),
+            FilterKind::Gaussian => {
+                // Shifted down to reach exactly zero at the radius.
+                let sigma = r / 3.0;
+                let gaussian = |x: f64| (-x * x / (2.0 * sigma * sigma)).exp();
+
+                (gaussian(d) - gaussian(r)).max(0.0)
+            }
+            FilterKind::Mitchell => {
+                if d.abs() >= r {
+                    return 0.0;
+                }
+                // The cubic is defined on [-2, 2].
+                let x = 2.0 * d.abs() / r;
+                let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b)) / 6.0
+                } else {
+                    ((-b - 6.0 * c) * x * x * x
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c)) / 6.0
+                }
+            }
+        }
+    }
+}
+
+impl Default for Filter {
+    /// One pixel wide box: every sample counts fully for the pixel it was
+    /// taken in and nowhere else.
+    fn default() -> Self {
+        Self::new(FilterKind::Box, FilterKind::Box.default_radius())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [FilterKind; 4] = [FilterKind::Box, FilterKind::Tent, FilterKind::Gaussian, FilterKind::Mitchell];
+
+    #[test]
+    fn weights_at_the_center() {
+        let at_center = |kind: FilterKind| Filter::new(kind, kind.default_radius()).evaluate(0.0, 0.0);
+
+        assert_eq!(at_center(FilterKind::Box), 1.0);
+        assert_eq!(at_center(FilterKind::Tent), 1.0);
+        // exp(-r^2 / 2 sigma^2) = exp(-4.5) is subtracted to reach zero at the radius.
+        assert!((at_center(FilterKind::Gaussian) - (1.0 - (-4.5f64).exp()).powi(2)).abs() < 1e-15);
+        // (6 - 2B) / 6 with B = 1/3, squared for the two axes.
+        assert!((at_center(FilterKind::Mitchell) - (8.0f64 / 9.0).powi(2)).abs() < 1e-15);
+    }
+
+    #[test]
+    fn weights_vanish_at_the_radius() {
+        for kind in KINDS {
+            for radius in [0.5, 1.0, 2.5] {
+                let filter = Filter::new(kind, radius);
+                for (dx, dy) in [(radius, 0.0), (0.0, radius), (radius + 0.1, 0.0), (0.0, -radius - 0.1)] {
+                    assert_eq!(filter.evaluate(dx, dy), 0.0, "{:?} at ({}, {})", kind, dx, dy);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn box_counts_samples_on_an_edge_once() {
+        let filter = Filter::default();
+
+        assert_eq!(filter.evaluate(-0.5, 0.0), 1.0);
+        assert_eq!(filter.evaluate(0.5, 0.0), 0.0);
+    }
+
+    #[test]
+    fn weights_sum_to_one_over_the_pixel_grid() {
+        // At their default radius, box, tent and Mitchell are partitions of
+        // unity: a sample's weights on the pixels around it add up to 1.
+        for kind in [FilterKind::Box, FilterKind::Tent, FilterKind::Mitchell] {
+            let filter = Filter::new(kind, kind.default_radius());
+            for i in 0..20 {
+                let (dx, dy) = (i as f64 / 20.0 - 0.5, 0.45 - i as f64 / 40.0);
+                let sum: f64 = (-3..=3)
+                    .flat_map(|m| (-3..=3).map(move |n| (m as f64, n as f64)))
+                    .map(|(m, n)| filter.evaluate(dx + m, dy + n))
+                    .sum();
+
+                assert!((sum - 1.0).abs() < 1e-12, "{:?} at ({}, {}): {}", kind, dx, dy, sum);
+            }
+        }
+    }
+
+    #[test]
+    fn filters_are_separable_and_symmetric() {
+        for kind in KINDS {
+            let filter = Filter::new(kind, kind.default_radius());
+            for (dx, dy) in [(0.1, 0.3), (0.25, -0.4), (-0.6, 0.05)] {
+                let product = filter.evaluate(dx, 0.0) * filter.evaluate(0.0, dy) / filter.evaluate(0.0, 0.0);
+
+                assert!((filter.evaluate(dx, dy) - product).abs() < 1e-12, "{:?}", kind);
+                if kind != FilterKind::Box {
+                    assert_eq!(filter.evaluate(dx, dy), filter.evaluate(-dx, -dy), "{:?}", kind);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pixels_are_normalized_by_their_weight() {
+        use crate::{Color3, image::Image};
+
+        // However the weights of the samples in a pixel add up, a constant
+        // radiance comes out unchanged.
+        for kind in KINDS {
+            let filter = Filter::new(kind, kind.default_radius());
+            let mut image = Image::new(1, 1);
+            for i in 0..7 {
+                let weight = filter.evaluate(0.13 * i as f64 - 0.4, 0.05 * i as f64);
+                image.splat(0, 0, Color3::new(0.25, 0.5, 2.0) * weight, weight);
+            }
+
+            let average = image.average(0, 0);
+            assert!((average - Color3::new(0.25, 0.5, 2.0)).length() < 1e-12, "{:?}: {:?}", kind, average);
+        }
+    }
+}
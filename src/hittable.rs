@@ -1,33 +1,63 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::{
     Point3,
+    aabb::Aabb,
     vec3::Vec3,
     material::{Lambertian, Material},
     Color3,
-    ray::Ray
+    ray::Ray,
+    sampler::Sampler
 };
 
-pub trait Hit {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+pub trait Hit: Send + Sync {
+    /// Closest intersection of `ray` within `[t_min, t_max]`. Surfaces
+    /// ignore `sampler`; objects with a random extent, such as participating
+    /// media, draw from it.
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, sampler: &mut dyn Sampler) -> Option<HitRecord>;
+
+    /// Box enclosing the object, or `None` if it is unbounded.
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// Density, per unit solid angle, with which [`Hit::random`] picks
+    /// `direction` from `origin`. Objects that cannot be sampled return 0.
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    /// Random direction from `origin` towards the object, used to sample
+    /// lights, or `None` if the object cannot be sampled.
+    fn random(&self, _origin: &Point3, _sampler: &mut dyn Sampler) -> Option<Vec3> {
+        None
+    }
 }
 
 pub struct HitRecord {
     pub p: Point3,
     pub normal: Vec3, // 法线
-    pub material: Rc<dyn Material>,
+    pub material: Arc<dyn Material>,
     pub t: f64,
+    pub u: f64, // surface coordinates of the hit point
+    pub v: f64,
     pub front_face: bool,
 }
 
+impl Default for HitRecord {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl HitRecord {
     pub fn new() -> Self {
         Self {
             p: Point3::new(0.0, 0.0, 0.0),
             normal: Vec3::new(0.0, 0.0, 0.0),
             // material: Box::new(Lambertian::new(Color3::new(0.0, 0.0, 0.0))),
-            material: Rc::new(Lambertian::new(Color3::new(0.0, 0.0, 0.0))),
+            material: Arc::new(Lambertian::new(Color3::new(0.0, 0.0, 0.0))),
             t: 0.0,
+            u: 0.0,
+            v: 0.0,
             front_face: true,
         }
     }
@@ -1,9 +1,15 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::{hittable::{Hit, HitRecord}, ray::Ray};
+use crate::{Point3, aabb::Aabb, hittable::{Hit, HitRecord}, ray::Ray, sampler::Sampler, vec3::Vec3};
 
 pub struct HittableList {
-    pub hittables_vec: Vec<Rc<dyn Hit>>
+    pub hittables_vec: Vec<Arc<dyn Hit>>
+}
+
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HittableList {
@@ -11,11 +17,11 @@ impl HittableList {
         Self { hittables_vec: Vec::new() }
     }
 
-    pub fn add(&mut self, item: Rc<dyn Hit>) {
+    pub fn add(&mut self, item: Arc<dyn Hit>) {
         self.hittables_vec.push(item);
     }
 
-    pub fn del(&mut self) -> Option<Rc<dyn Hit>> {
+    pub fn del(&mut self) -> Option<Arc<dyn Hit>> {
         if let Some(item) = self.hittables_vec.pop() {
             Some(item)
         } else {
@@ -23,13 +29,13 @@ impl HittableList {
         }
     }
 
-    pub fn is_hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    pub fn is_hit(&self, ray: &Ray, t_min: f64, t_max: f64, sampler: &mut dyn Sampler) -> Option<HitRecord> {
         let mut hit_record = HitRecord::new();
         let mut closest_so_far = t_max;
         let mut hit_anything = false;
 
         for hittable_obj in &self.hittables_vec {
-            if let Some(hit_rec) = hittable_obj.hit(ray, t_min, closest_so_far) {
+            if let Some(hit_rec) = hittable_obj.hit(ray, t_min, closest_so_far, sampler) {
                 hit_anything = true;
                 closest_so_far = hit_rec.t;
                 hit_record = hit_rec;
@@ -43,3 +49,46 @@ impl HittableList {
         }
     }
 }
+
+impl Hit for HittableList {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        self.is_hit(ray, t_min, t_max, sampler)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut output_box: Option<Aabb> = None;
+
+        for hittable_obj in &self.hittables_vec {
+            let temp_box = hittable_obj.bounding_box()?;
+            output_box = Some(match output_box {
+                Some(b) => Aabb::surrounding_box(&b, &temp_box),
+                None => temp_box,
+            });
+        }
+
+        output_box
+    }
+
+    /// Picks one of the objects uniformly, then samples it.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        if self.hittables_vec.is_empty() {
+            return 0.0;
+        }
+
+        let weight = 1.0 / self.hittables_vec.len() as f64;
+
+        self.hittables_vec.iter()
+            .map(|hittable_obj| weight * hittable_obj.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let count = self.hittables_vec.len();
+        if count == 0 {
+            return None;
+        }
+
+        let index = ((sampler.random() * count as f64) as usize).min(count - 1);
+        self.hittables_vec[index].random(origin, sampler)
+    }
+}
@@ -0,0 +1,475 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{Color3, color, exr::{self, ExrPixelType}, pfm, png, tonemap::OutputTransform};
+
+/// File formats the framebuffer can be encoded to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    PpmAscii,
+    PpmBinary,
+    Png,
+    Pfm,
+    /// OpenEXR with half-float channels.
+    Exr,
+    /// OpenEXR with 32-bit float channels.
+    ExrFloat,
+}
+
+impl ImageFormat {
+    /// Picks the format from a file extension; `.ppm` keeps the ASCII P3
+    /// output the renderer has always produced.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+
+        match ext.as_str() {
+            "ppm" => Some(ImageFormat::PpmAscii),
+            "png" => Some(ImageFormat::Png),
+            "pfm" => Some(ImageFormat::Pfm),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None,
+        }
+    }
+
+    /// Parses the names accepted by the `--format` flag.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" | "p3" => Some(ImageFormat::PpmAscii),
+            "p6" => Some(ImageFormat::PpmBinary),
+            "png" => Some(ImageFormat::Png),
+            "pfm" => Some(ImageFormat::Pfm),
+            "exr" => Some(ImageFormat::Exr),
+            "exr-float" => Some(ImageFormat::ExrFloat),
+            _ => None,
+        }
+    }
+}
+
+/// Running mean and variance of a stream of values, updated one value at a
+/// time with Welford's algorithm.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Welford {
+    pub count: u32,
+    pub mean: f64,
+    /// Sum of squared differences from the mean.
+    pub m2: f64,
+}
+
+impl Welford {
+    pub fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Unbiased sample variance; zero until there are two values.
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 { 0.0 } else { self.m2 / (self.count - 1) as f64 }
+    }
+}
+
+/// In-memory framebuffer holding the filter-weighted sum of the linear colors
+/// that reached each pixel and the sum of their weights, together with the
+/// number of samples taken in it and running statistics of their luminance
+/// for adaptive sampling.
+///
+/// Pixels are stored row-major with row 0 at the top of the image, which is
+/// the order PPM (and most other formats) expect.
+#[derive(Clone)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color3>,
+    weights: Vec<f64>,
+    samples: Vec<u32>,
+    stats: Vec<Welford>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = width as usize * height as usize;
+
+        Self {
+            width,
+            height,
+            pixels: vec![Color3::new(0.0, 0.0, 0.0); len],
+            weights: vec![0.0; len],
+            samples: vec![0; len],
+            stats: vec![Welford::default(); len],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    pub fn add_sample(&mut self, x: u32, y: u32, color: Color3) {
+        self.accumulate(x, y, color, 1);
+        let idx = self.index(x, y);
+        self.stats[idx].add(color::luminance(color));
+    }
+
+    /// Adds an already summed block of `samples` samples to pixel (x, y),
+    /// each with weight 1. Its luminance statistics are left to
+    /// [`Image::set_pixel_stats`].
+    pub fn accumulate(&mut self, x: u32, y: u32, color_sum: Color3, samples: u32) {
+        self.splat(x, y, color_sum, samples as f64);
+        self.count_samples(x, y, samples);
+    }
+
+    /// Adds filtered contributions to pixel (x, y): `weighted_sum` is the sum
+    /// of each color times its filter weight and `weight` the sum of the
+    /// weights. The samples themselves are counted where they were taken,
+    /// with [`Image::count_samples`].
+    pub fn splat(&mut self, x: u32, y: u32, weighted_sum: Color3, weight: f64) {
+        let idx = self.index(x, y);
+        self.pixels[idx] += weighted_sum;
+        self.weights[idx] += weight;
+    }
+
+    pub fn count_samples(&mut self, x: u32, y: u32, samples: u32) {
+        let idx = self.index(x, y);
+        self.samples[idx] += samples;
+    }
+
+    /// Weighted sum of all contributions accumulated in pixel (x, y).
+    pub fn pixel_sum(&self, x: u32, y: u32) -> Color3 {
+        self.pixels[self.index(x, y)]
+    }
+
+    /// Sum of the filter weights of the contributions to pixel (x, y).
+    pub fn weight(&self, x: u32, y: u32) -> f64 {
+        self.weights[self.index(x, y)]
+    }
+
+    pub fn sample_count(&self, x: u32, y: u32) -> u32 {
+        self.samples[self.index(x, y)]
+    }
+
+    /// Number of samples over all pixels.
+    pub fn total_samples(&self) -> u64 {
+        self.samples.iter().map(|&n| n as u64).sum()
+    }
+
+    /// Luminance statistics of the samples in pixel (x, y).
+    pub fn pixel_stats(&self, x: u32, y: u32) -> Welford {
+        self.stats[self.index(x, y)]
+    }
+
+    pub fn set_pixel_stats(&mut self, x: u32, y: u32, stats: Welford) {
+        let idx = self.index(x, y);
+        self.stats[idx] = stats;
+    }
+
+    /// Debug view of where the samples went: each pixel's sample count
+    /// relative to the largest one, from dark blue through green and yellow
+    /// to red.
+    pub fn sample_heatmap(&self) -> Image {
+        const STOPS: [(f64, f64, f64); 5] = [
+            (0.0, 0.0, 0.3),
+            (0.0, 0.4, 1.0),
+            (0.0, 1.0, 0.3),
+            (1.0, 1.0, 0.0),
+            (1.0, 0.0, 0.0),
+        ];
+        let max = self.samples.iter().copied().max().unwrap_or(0).max(1) as f64;
+        let mut heatmap = Image::new(self.width, self.height);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let t = self.sample_count(x, y) as f64 / max * (STOPS.len() - 1) as f64;
+                let i = (t as usize).min(STOPS.len() - 2);
+                let f = t - i as f64;
+                let (a, b) = (STOPS[i], STOPS[i + 1]);
+                let color = Color3::new(
+                    color::srgb_to_linear(a.0 + (b.0 - a.0) * f),
+                    color::srgb_to_linear(a.1 + (b.1 - a.1) * f),
+                    color::srgb_to_linear(a.2 + (b.2 - a.2) * f),
+                );
+
+                // The stops are display colors; store them linear so the
+                // default output transform puts them back on screen.
+                heatmap.accumulate(x, y, color, 1);
+            }
+        }
+
+        heatmap
+    }
+
+    /// Weighted average linear radiance of pixel (x, y), black if nothing
+    /// has reached it yet.
+    pub fn average(&self, x: u32, y: u32) -> Color3 {
+        let weight = self.weight(x, y);
+
+        // Filters with negative lobes can leave a sparsely sampled pixel
+        // with no positive weight.
+        if weight <= 0.0 {
+            Color3::new(0.0, 0.0, 0.0)
+        } else {
+            self.pixel_sum(x, y) / weight
+        }
+    }
+
+    /// 8-bit sRGB pixel after `transform`, as written by the LDR encoders.
+    pub fn rgb8(&self, x: u32, y: u32, transform: &OutputTransform) -> [u8; 3] {
+        transform.to_rgb8(self.average(x, y))
+    }
+
+    /// Encodes the image as an ASCII PPM (P3).
+    pub fn write_ppm_p3<W: Write>(&self, out: &mut W, transform: &OutputTransform) -> io::Result<()> {
+        writeln!(out, "P3\n{} {}\n255", self.width, self.height)?;
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let [r, g, b] = self.rgb8(x, y, transform);
+                writeln!(out, "{} {} {}", r, g, b)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// All pixels as 8-bit sRGB triplets after `transform`, top row first.
+    pub fn to_rgb8_bytes(&self, transform: &OutputTransform) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 3);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                bytes.extend_from_slice(&self.rgb8(x, y, transform));
+            }
+        }
+
+        bytes
+    }
+
+    /// Unclamped average radiance of every pixel, top row first.
+    pub fn to_linear_rgb(&self) -> Vec<[f32; 3]> {
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let c = self.average(x, y);
+                pixels.push([c.x() as f32, c.y() as f32, c.z() as f32]);
+            }
+        }
+
+        pixels
+    }
+
+    /// Encodes the image as a binary PPM (P6).
+    pub fn write_ppm_p6<W: Write>(&self, out: &mut W, transform: &OutputTransform) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(&self.to_rgb8_bytes(transform))
+    }
+
+    /// Encodes the image as an 8-bit sRGB PNG.
+    pub fn write_png<W: Write>(&self, out: &mut W, transform: &OutputTransform) -> io::Result<()> {
+        png::write_png(out, self.width, self.height, &self.to_rgb8_bytes(transform))
+    }
+
+    /// Encodes the image in `format`. The 8-bit formats go through
+    /// `transform`; PFM and EXR store the linear averages untouched.
+    pub fn write<W: Write>(&self, out: &mut W, format: ImageFormat, transform: &OutputTransform) -> io::Result<()> {
+        match format {
+            ImageFormat::PpmAscii => self.write_ppm_p3(out, transform),
+            ImageFormat::PpmBinary => self.write_ppm_p6(out, transform),
+            ImageFormat::Png => self.write_png(out, transform),
+            ImageFormat::Pfm => {
+                pfm::write_pfm(out, self.width, self.height, &self.to_linear_rgb())
+            }
+            ImageFormat::Exr => {
+                exr::write_exr(out, self.width, self.height, &self.to_linear_rgb(), ExrPixelType::Half)
+            }
+            ImageFormat::ExrFloat => {
+                exr::write_exr(out, self.width, self.height, &self.to_linear_rgb(), ExrPixelType::Float)
+            }
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: ImageFormat, transform: &OutputTransform) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+
+        self.write(&mut out, format, transform)?;
+        out.flush()
+    }
+}
+
+/// Decodes an ASCII (P3) or binary (P6) PPM into 8-bit RGB, top row first.
+/// Samples with a `maxval` other than 255 are rescaled.
+pub fn read_ppm(data: &[u8]) -> io::Result<(u32, u32, Vec<u8>)> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid PPM: {}", message));
+    let mut pos = 0;
+
+    // Header fields are whitespace separated and may be interleaved with `#` comments.
+    let next_token = |pos: &mut usize| -> io::Result<String> {
+        loop {
+            match data.get(*pos) {
+                Some(b'#') => {
+                    while data.get(*pos).is_some_and(|&b| b != b'\n') {
+                        *pos += 1;
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => *pos += 1,
+                Some(_) => break,
+                None => return Err(invalid("unexpected end of data")),
+            }
+        }
+        let start = *pos;
+        while data.get(*pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+            *pos += 1;
+        }
+        Ok(String::from_utf8_lossy(&data[start..*pos]).into_owned())
+    };
+
+    let magic = next_token(&mut pos)?;
+    if magic != "P3" && magic != "P6" {
+        return Err(invalid("expected a P3 or P6 header"));
+    }
+    let number = |pos: &mut usize| -> io::Result<u32> {
+        next_token(pos)?.parse().map_err(|_| invalid("bad number"))
+    };
+    let width = number(&mut pos)?;
+    let height = number(&mut pos)?;
+    let maxval = number(&mut pos)?;
+    if maxval == 0 || maxval > 65535 {
+        return Err(invalid("maxval must be between 1 and 65535"));
+    }
+
+    // The header is untrusted: check the size against the data before
+    // allocating anything for it.
+    let count = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|n| n.checked_mul(3))
+        .ok_or_else(|| invalid("image dimensions are too large"))?;
+    let sample_size = if maxval < 256 { 1 } else { 2 };
+    let remaining = data.len().saturating_sub(pos);
+    let fits = if magic == "P3" {
+        // Every ASCII sample takes a digit, and all but the last a separator.
+        count.checked_mul(2).is_some_and(|bytes| bytes <= remaining + 1)
+    } else {
+        // A single whitespace byte separates the header from the samples.
+        count.checked_mul(sample_size).is_some_and(|bytes| bytes < remaining)
+    };
+    if !fits {
+        return Err(invalid("image data is too short"));
+    }
+
+    let scale = |value: u32| -> io::Result<u8> {
+        if value > maxval {
+            return Err(invalid("sample exceeds maxval"));
+        }
+        Ok((value * 255 / maxval) as u8)
+    };
+    let mut rgb = Vec::with_capacity(count);
+
+    if magic == "P3" {
+        for _ in 0..count {
+            rgb.push(scale(number(&mut pos)?)?);
+        }
+    } else {
+        pos += 1;
+        let samples = data.get(pos..pos + count * sample_size)
+            .ok_or_else(|| invalid("image data is too short"))?;
+        for sample in samples.chunks(sample_size) {
+            let value = sample.iter().fold(0, |acc, &b| (acc << 8) | b as u32);
+            rgb.push(scale(value)?);
+        }
+    }
+
+    Ok((width, height, rgb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ppm_error(data: &[u8]) -> String {
+        read_ppm(data).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn ppm_round_trip() {
+        let mut image = Image::new(3, 2);
+        for (i, (x, y)) in [(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)].into_iter().enumerate() {
+            let v = i as f64 / 5.0;
+            image.accumulate(x, y, Color3::new(v, 1.0 - v, 0.5), 1);
+        }
+        let transform = OutputTransform::default();
+        let expected = image.to_rgb8_bytes(&transform);
+
+        for format in [ImageFormat::PpmAscii, ImageFormat::PpmBinary] {
+            let mut out = Vec::new();
+            image.write(&mut out, format, &transform).unwrap();
+            assert_eq!(read_ppm(&out).unwrap(), (3, 2, expected.clone()));
+        }
+    }
+
+    #[test]
+    fn ppm_rescales_maxval_and_skips_comments() {
+        let (width, height, rgb) = read_ppm(b"P3\n# a comment\n1 1\n15\n15 0 5\n").unwrap();
+        assert_eq!((width, height, rgb), (1, 1, vec![255, 0, 85]));
+
+        let (_, _, rgb) = read_ppm(b"P6 1 1 65535\n\xFF\xFF\x00\x00\x80\x00").unwrap();
+        assert_eq!(rgb, [255, 0, 127]);
+    }
+
+    #[test]
+    fn ppm_rejects_truncated_data() {
+        assert_eq!(ppm_error(b"P6 2 1 255\n\x01\x02\x03\x04\x05"), "invalid PPM: image data is too short");
+        assert_eq!(ppm_error(b"P3 2 1 255\n1 2 3 4 5"), "invalid PPM: image data is too short");
+        assert_eq!(ppm_error(b"P3 1 1 255\n1 2 300"), "invalid PPM: sample exceeds maxval");
+        assert_eq!(ppm_error(b"P6 1"), "invalid PPM: unexpected end of data");
+    }
+
+    #[test]
+    fn ppm_rejects_huge_headers_before_allocating() {
+        assert_eq!(ppm_error(b"P6 4294967295 4294967295 255\n"), "invalid PPM: image dimensions are too large");
+        assert_eq!(ppm_error(b"P6 1048576 1048576 255\n\0\0\0"), "invalid PPM: image data is too short");
+        assert_eq!(ppm_error(b"P3 1048576 1048576 255\n1 2 3"), "invalid PPM: image data is too short");
+    }
+
+    fn two_pass(values: &[f64]) -> (f64, f64) {
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / (n - 1.0);
+
+        (mean, variance)
+    }
+
+    #[test]
+    fn welford_matches_a_two_pass_computation() {
+        // A large offset is where the naive sum-of-squares formula falls apart.
+        for offset in [0.0, 1e9] {
+            let values: Vec<f64> = (0..1000).map(|i| offset + ((i * 7919) % 101) as f64 * 0.01).collect();
+            let mut stats = Welford::default();
+            values.iter().for_each(|&v| stats.add(v));
+
+            let (mean, variance) = two_pass(&values);
+            assert_eq!(stats.count, 1000);
+            assert!((stats.mean - mean).abs() <= 1e-12 * mean.abs().max(1.0), "{} vs {}", stats.mean, mean);
+            assert!((stats.variance() - variance).abs() <= 1e-6 * variance, "{} vs {}", stats.variance(), variance);
+        }
+    }
+
+    #[test]
+    fn welford_variance_needs_two_values() {
+        let mut stats = Welford::default();
+        assert_eq!(stats.variance(), 0.0);
+        stats.add(3.0);
+        assert_eq!((stats.mean, stats.variance()), (3.0, 0.0));
+        stats.add(5.0);
+        assert_eq!((stats.mean, stats.variance()), (4.0, 2.0));
+    }
+}
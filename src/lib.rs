@@ -1,12 +1,35 @@
 pub mod vec3;
 pub mod color;
+pub mod tonemap;
+pub mod image;
 pub mod ray;
 pub mod hittable;
 pub mod sphere;
+pub mod moving_sphere;
+pub mod triangle;
+pub mod quad;
+pub mod transform;
+pub mod constant_medium;
+pub mod obj;
 pub mod hittable_list;
+pub mod aabb;
+pub mod bvh;
 pub mod rtweekend;
+pub mod sampler;
+pub mod filter;
 pub mod camera;
 pub mod material;
+pub mod pdf;
+pub mod texture;
+pub mod perlin;
+pub mod render;
+pub mod checkpoint;
+pub mod scenes;
+pub mod scene_file;
+pub mod png;
+pub mod pfm;
+pub mod exr;
+pub mod zlib;
 
 pub type Point3 = vec3::Vec3;
 pub type Color3 = vec3::Vec3;
@@ -1,175 +1,241 @@
-use std::rc::Rc;
+mod cli;
+
+use std::{env, fs, io::{self, BufWriter, Write}, process, sync::Arc};
 
 use create_image::{
-    Color3,
-    Point3,
-    vec3::Vec3,
-    hittable_list::HittableList,
-    ray::Ray,
-    camera::Camera,
-    rtweekend,
-    color,
-    material::{Lambertian, Dielectric, Metal},
-    sphere::Sphere
+    bvh::BvhNode,
+    checkpoint::{Checkpoint, IntegratorSettings, SamplingSettings, SceneId},
+    hittable::Hit,
+    image::{Image, ImageFormat},
+    render::{Pass, PathStats, Renderer},
+    scene_file,
+    scenes,
+    tonemap::OutputTransform,
 };
-// use std::f64::consts::FRAC_PI_4;
-
 
-fn ray_color(ray: &Ray, world: &HittableList, depth: i32) -> Color3 {
-    // If we've exceeded the ray bounce limit, no more light is gathered.
-    if depth <= 0 {
-        return Color3::new(0.0, 0.0, 0.0);
-    }
+fn main() {
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(cli::Command::Render(options)) => *options,
+        Ok(cli::Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\nTry '--help' for more information.", err);
+            process::exit(2);
+        }
+    };
 
-    if let Some(hit_rec) = world.is_hit(ray, 0.001, f64::INFINITY) {
-        if let Some((attenuation, scattered)) =
-            hit_rec.material.scatter(ray, &hit_rec)
-        {
-            return attenuation * ray_color(&scattered, world, depth-1);
-        } else {
-            return Color3::new(0.0, 0.0, 0.0);
+    // World
+    let (scene, scene_id) = match scenes::by_name(&options.scene, options.seed) {
+        Some(scene) => (scene, SceneId::new(options.scene.as_str(), &[])),
+        None => match scene_file::load(&options.scene) {
+            Ok(scene) => (scene, scene_file_id(&options.scene)),
+            Err(err) => {
+                eprintln!("error: {}: {}", options.scene, err);
+                process::exit(1);
+            }
+        },
+    };
+
+    let settings = match options.resolve(&scene.render) {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(2);
         }
-    }
+    };
+
+    // Acceleration structure; an empty or unbounded world is scanned linearly.
+    let bvh;
+    let world: &dyn Hit = match BvhNode::new(&scene.world) {
+        Ok(node) => {
+            bvh = node;
+            &bvh
+        }
+        Err(_) => &scene.world,
+    };
 
-    let unit_direction = Color3::unit_vector(*ray.direction());
-    let t = 0.5 * (unit_direction.y() + 1.0);
+    // Camera
+    let camera = scene.camera.build(settings.aspect_ratio);
 
-    // Color3::new(1.0, 1.0, 1.0).multiply_coef(1.0 - t)
-    //     + Color3::new(0.5, 0.7, 1.0).multiply_coef(t)
-    Color3::new(1.0, 1.0, 1.0) * (1.0 - t) + Color3::new(0.5, 0.7, 1.0) * t
-}
+    // Render
+    let mut renderer = Renderer::new(
+        settings.width,
+        settings.height,
+        settings.samples_per_pixel,
+        settings.max_depth
+    );
+    renderer.roulette_depth = options.roulette_depth;
+    renderer.adaptive = options.adaptive;
+    renderer.sampler = options.sampler;
+    renderer.filter = options.filter;
+    renderer.seed = options.seed;
+    renderer.background = scene.background;
+    renderer.progress = Some(Arc::new(|remaining| eprint!("\rScanlines remaining: {} ", remaining)));
+    if let Some(threads) = options.threads {
+        renderer.threads = threads;
+    }
 
-fn random_scene() -> HittableList {
-    let mut world = HittableList::new();
-    let ground_material = Lambertian::new(Color3::new(0.5, 0.5, 0.5));
-
-    world.add(Rc::new(Sphere::new(
-        Point3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        Rc::new(ground_material)
-    )));
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_material = rtweekend::random();
-            let center = Point3::new(
-                a as f64 + 0.9 * rtweekend::random(),
-                0.2,
-                b as f64 + 0.9 * rtweekend::random()
-            );
-
-            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                if choose_material < 0.8 {
-                    // diffuse
-                    let albedo = Color3::random_vec3() * Color3::random_vec3();
-                    let sphere_material = Lambertian::new(albedo);
-
-                    world.add(Rc::new(Sphere::new(
-                        center,
-                        0.2,
-                        Rc::new(sphere_material)
-                    )));
-                } else if choose_material < 0.95 {
-                    // metal
-                    let albedo = Color3::random_vec3_in_range(0.5, 1.0);
-                    let fuzz = rtweekend::random_in_range(0.0, 0.5);
-                    let sphere_material = Metal::new(albedo, fuzz);
-
-                    world.add(Rc::new(Sphere::new(
-                        center,
-                        0.2,
-                        Rc::new(sphere_material)
-                    )));
-                } else {
-                    // glass
-                    let sphere_material = Dielectric::new(1.5);
-
-                    world.add(Rc::new(Sphere::new(
-                        center,
-                        0.2,
-                        Rc::new(sphere_material)
-                    )));
-                }
+    let sampling = SamplingSettings {
+        sampler: options.sampler,
+        filter: options.filter,
+        adaptive: options.adaptive,
+        pass_samples: options.pass_samples,
+        total_samples: settings.samples_per_pixel,
+    };
+    let integrator = IntegratorSettings {
+        max_depth: settings.max_depth,
+        roulette_depth: options.roulette_depth,
+    };
+
+    // Progress so far, picked up from the checkpoint file when resuming.
+    let mut progress = match options.checkpoint.as_ref().filter(|path| path.exists()) {
+        Some(path) => match Checkpoint::load(path) {
+            Ok(progress) => progress,
+            Err(err) => {
+                eprintln!("error: {}: {}", path.display(), err);
+                process::exit(1);
             }
-        }
+        },
+        None => Checkpoint {
+            image: Image::new(settings.width, settings.height),
+            seed: options.seed,
+            passes: 0,
+            samples_per_pixel: 0,
+            settings: sampling,
+            integrator,
+            scene: scene_id.clone(),
+        },
+    };
+    if (progress.image.width(), progress.image.height()) != (settings.width, settings.height) {
+        eprintln!(
+            "error: the checkpoint is {}x{}, but the image is {}x{}",
+            progress.image.width(), progress.image.height(), settings.width, settings.height
+        );
+        process::exit(2);
+    }
+    if progress.seed != options.seed {
+        eprintln!("error: the checkpoint was rendered with --seed {}", progress.seed);
+        process::exit(2);
+    }
+    if progress.scene.name != scene_id.name {
+        eprintln!("error: the checkpoint was rendered from scene {}", progress.scene.name);
+        process::exit(2);
+    }
+    if progress.scene.hash != scene_id.hash {
+        eprintln!("error: {} has changed since the checkpoint was written", scene_id.name);
+        process::exit(2);
+    }
+    if progress.integrator != integrator {
+        let roulette = progress.integrator.roulette_depth.map_or("off".to_string(), |depth| depth.to_string());
+        eprintln!(
+            "error: the checkpoint was rendered with --max-depth {} --roulette {}",
+            progress.integrator.max_depth, roulette
+        );
+        process::exit(2);
+    }
+    if progress.settings != sampling {
+        eprintln!("error: the checkpoint was rendered with {}", sampling_options(&progress.settings));
+        process::exit(2);
+    }
+    if progress.passes > 0 {
+        eprintln!("Resuming after pass {} ({} spp)", progress.passes, progress.samples_per_pixel);
     }
 
-    let material1 = Dielectric::new(1.5);
-    let material2 = Lambertian::new(Color3::new(0.4, 0.2, 0.1));
-    let material3 = Metal::new(Color3::new(0.7, 0.6, 0.5), 0.0);
-
-    world.add(Rc::new(Sphere::new(
-        Point3::new(0.0, 1.0, 0.0),
-        1.0,
-        Rc::new(material1)
-    )));
-
-    world.add(Rc::new(Sphere::new(
-        Point3::new(-4.0, 1.0, 0.0),
-        1.0,
-        Rc::new(material2)
-    )));
-
-    world.add(Rc::new(Sphere::new(
-        Point3::new(4.0, 1.0, 0.0),
-        1.0,
-        Rc::new(material3)
-    )));
-
-    world
-}
-
-fn main() {
-    // Image
-    const ASPECT_RATIO: f64 = 3.0 / 2.0;
-    const IMAGE_WIDTH: u32 = 1200;
-    const IMAGE_HEIGHT: u32 = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as u32;
-    const SAMPLES_PER_PIXEL: u32 = 500;
-    const MAX_DEPTH: i32 = 50;
-
-    // World
-    let world = random_scene();
+    // Previews only make sense when there is more than one pass.
+    let preview = match (&options.preview, &options.output) {
+        (Some(path), _) => Some((path, ImageFormat::from_path(path).unwrap())),
+        (None, Some(path)) if options.pass_samples.is_some() => Some((path, options.format)),
+        _ => None,
+    };
+
+    let mut stats = PathStats::default();
+    while progress.samples_per_pixel < settings.samples_per_pixel {
+        let remaining = settings.samples_per_pixel - progress.samples_per_pixel;
+        let pass = Pass {
+            index: progress.passes,
+            samples: options.pass_samples.unwrap_or(remaining).min(remaining),
+        };
+        stats.merge(&renderer.render_pass(world, &scene.lights, &camera, pass, &mut progress.image));
+        progress.passes += 1;
+        progress.samples_per_pixel += pass.samples;
+
+        if let Some((path, format)) = preview {
+            eprint!("\rPass {} done, {} spp", progress.passes, progress.samples_per_pixel);
+            if let Err(err) = progress.image.save(path, format, &options.transform) {
+                eprintln!("\nFailed to write preview: {}", err);
+                process::exit(1);
+            }
+        }
+        if let Some(path) = &options.checkpoint {
+            if let Err(err) = progress.save(path) {
+                eprintln!("\nFailed to write checkpoint: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+    if options.stats {
+        eprintln!("\n{}", stats);
+        if options.adaptive.is_some() {
+            let pixels = settings.width as f64 * settings.height as f64;
+            eprintln!("{:.1} samples per pixel on average", progress.image.total_samples() as f64 / pixels);
+        }
+    }
+    if let Some(path) = &options.heatmap {
+        let heatmap = progress.image.sample_heatmap();
+        if let Err(err) = heatmap.save(path, ImageFormat::from_path(path).unwrap(), &OutputTransform::default()) {
+            eprintln!("\nFailed to write heatmap: {}", err);
+            process::exit(1);
+        }
+    }
 
-    // Camera
-    let lookfrom = Point3::new(13.0, 2.0, 3.0);
-    let lookat = Point3::new(0.0, 0.0, 0.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.1;
-
-    let camera = Camera::new(
-        lookfrom,
-        lookat,
-        vup, 20.0,
-        ASPECT_RATIO,
-        aperture,
-        dist_to_focus
-    );
+    let image = progress.image;
 
-    // Render
-    let head = format!("P3\n{} {}\n255", IMAGE_WIDTH, IMAGE_HEIGHT);
-    let width_minus_one = IMAGE_WIDTH - 1;
-    let height_minus_one = IMAGE_HEIGHT - 1;
+    let result = match &options.output {
+        Some(path) => image.save(path, options.format, &options.transform),
+        None => {
+            let mut out = BufWriter::new(io::stdout().lock());
+            image.write(&mut out, options.format, &options.transform).and_then(|_| out.flush())
+        }
+    };
 
-    println!("{}", head);
+    if let Err(err) = result {
+        eprintln!("\nFailed to write image: {}", err);
+        process::exit(1);
+    }
 
-    for j in (0..IMAGE_HEIGHT).rev() {
-        eprint!("\rScanlines remaining: {} ", j);
+    eprintln!("\nDone.");
+}
 
-        for i in 0..IMAGE_WIDTH {
-            let mut pixel_color = Color3::new(0.0, 0.0, 0.0);
+/// Identifies the scene file at `path` by its absolute path, so that resuming
+/// from another directory still matches, and by its contents.
+fn scene_file_id(path: &str) -> SceneId {
+    let name = fs::canonicalize(path).map_or_else(|_| path.to_string(), |path| path.display().to_string());
 
-            for _ in 0..SAMPLES_PER_PIXEL {
-                let u = (i as f64 + rtweekend::random()) / width_minus_one as f64;
-                let v = (j as f64 + rtweekend::random()) / height_minus_one as f64;
-                let ray = camera.get_ray(u, v);
+    SceneId::new(name, &fs::read(path).unwrap_or_default())
+}
 
-                pixel_color += ray_color(&ray, &world, MAX_DEPTH);
-            }
+/// Command-line options that reproduce `settings`.
+fn sampling_options(settings: &SamplingSettings) -> String {
+    let mut options = format!(
+        "--spp {} --sampler {} --filter {} --filter-radius {}",
+        settings.total_samples, settings.sampler.name(), settings.filter.kind.name(), settings.filter.radius
+    );
+    if let Some(adaptive) = settings.adaptive {
+        options += &format!(" --adaptive {} --min-spp {}", adaptive.threshold, adaptive.min_samples);
+    }
+    if let Some(samples) = settings.pass_samples {
+        options += &format!(" --pass-spp {}", samples);
+    }
 
-            color::write_color(pixel_color, SAMPLES_PER_PIXEL);
-        }
+    let missing: Vec<&str> = [("--adaptive", settings.adaptive.is_none()), ("--pass-spp", settings.pass_samples.is_none())]
+        .into_iter()
+        .filter_map(|(option, missing)| missing.then_some(option))
+        .collect();
+    if !missing.is_empty() {
+        options += &format!(" (without {})", missing.join(" or "));
     }
 
-    eprintln!("\nDone.");
+    options
 }
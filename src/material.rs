@@ -1,20 +1,56 @@
-use crate::{Color3, hittable::HitRecord, ray::Ray, rtweekend, vec3::Vec3};
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    Color3,
+    hittable::HitRecord,
+    pdf::{CosinePdf, Pdf, SpherePdf},
+    ray::Ray,
+    rtweekend,
+    sampler::Sampler,
+    texture::{SolidColor, Texture},
+    vec3::Vec3,
+};
 // use dyn_clone::DynClone;
 
 // pub trait Material: DynClone {
 //     fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color3, Ray)>;
 // }
 
-pub trait Material {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color3, Ray)>;
+/// How a material scatters an incoming ray.
+pub enum ScatterRecord {
+    /// A single chosen direction, as for mirrors and glass. Light sampling
+    /// cannot help here, so the ray is followed as is.
+    Specular { attenuation: Color3, ray: Ray },
+    /// A distribution of directions that the integrator samples and combines
+    /// with light sampling; see [`Material::scattering_pdf`].
+    Diffuse { attenuation: Color3, pdf: Box<dyn Pdf> },
+}
+
+pub trait Material: Send + Sync {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord>;
+
+    /// Density with which the material scatters light into `scattered`'s
+    /// direction; the BSDF of diffuse materials is `attenuation` times this.
+    fn scattering_pdf(&self, _ray_in: &Ray, _hit_record: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+
+    /// Light given off at the hit point; black for everything but lights.
+    fn emitted(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Color3 {
+        Color3::new(0.0, 0.0, 0.0)
+    }
 }
 
 pub struct Lambertian {
-    pub albedo: Color3
+    pub albedo: Arc<dyn Texture>
 }
 
 impl Lambertian {
     pub fn new(albedo: Color3) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>) -> Self {
         Self { albedo }
     }
 }
@@ -26,25 +62,30 @@ impl Lambertian {
 // }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color3, Ray)> {
-        let mut scatter_direction = hit_record.normal + Vec3::random_unit_vector();
+    fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord, _sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        let attenuation = self.albedo.value(hit_record.u, hit_record.v, &hit_record.p);
 
-        // Catch degenerate scatter direction
-        if scatter_direction.near_zero() {
-            scatter_direction = hit_record.normal;
-        }
+        Some(ScatterRecord::Diffuse { attenuation, pdf: Box::new(CosinePdf::new(&hit_record.normal)) })
+    }
 
-        Some((self.albedo, Ray::new(hit_record.p, scatter_direction)))
+    fn scattering_pdf(&self, _ray_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> f64 {
+        let cosine = Vec3::dot(&hit_record.normal, &Vec3::unit_vector(*scattered.direction()));
+
+        (cosine / PI).max(0.0)
     }
 }
 
 pub struct Metal {
-    pub albedo: Color3,
+    pub albedo: Arc<dyn Texture>,
     pub fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: Color3, fuzz: f64) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>, fuzz: f64) -> Self {
         Self {
             albedo,
             fuzz: rtweekend::clamp(fuzz, 0.0, 1.0),
@@ -62,17 +103,18 @@ impl Metal {
 // }
 
 impl Material for Metal {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color3, Ray)> {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
         let reflected = Vec3::reflect(&Vec3::unit_vector(*ray_in.direction()), &hit_record.normal);
-        let scattered = Ray::new(
+        let scattered = Ray::with_time(
             hit_record.p, 
             // reflected + Vec3::random_in_unit_sphere().multiply_coef(self.fuzz)
-            reflected + Vec3::random_in_unit_sphere() * self.fuzz
+            reflected + Vec3::random_in_unit_sphere(sampler) * self.fuzz,
+            ray_in.time()
         );
-        let attenuation = self.albedo;
+        let attenuation = self.albedo.value(hit_record.u, hit_record.v, &hit_record.p);
 
         if Vec3::dot(scattered.direction(), &hit_record.normal) > 0.0 {
-            Some((attenuation, scattered))
+            Some(ScatterRecord::Specular { attenuation, ray: scattered })
         } else {
             None
         }
@@ -103,7 +145,7 @@ impl Dielectric {
 // }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color3, Ray)> {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
         let refraction_ratio = if hit_record.front_face {
             1.0 / self.ir
         } else {
@@ -117,12 +159,64 @@ impl Material for Dielectric {
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
 
         let direction = if cannot_refract ||
-            Self::reflectance(cos_theta, refraction_ratio) > rtweekend::random() {
+            Self::reflectance(cos_theta, refraction_ratio) > sampler.random() {
             Vec3::reflect(&unit_direction, &hit_record.normal)
         } else {
             Vec3::refract(&unit_direction, &hit_record.normal, refraction_ratio)
         };
 
-        Some((Color3::new(1.0, 1.0, 1.0), Ray::new(hit_record.p, direction)))
+        Some(ScatterRecord::Specular {
+            attenuation: Color3::new(1.0, 1.0, 1.0),
+            ray: Ray::with_time(hit_record.p, direction, ray_in.time()),
+        })
+    }
+}
+
+/// Area light: emits `emit` from both sides of the surface and scatters nothing.
+pub struct DiffuseLight {
+    pub emit: Color3
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color3) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray_in: &Ray, _hit_record: &HitRecord, _sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Color3 {
+        self.emit
+    }
+}
+
+/// Phase function of a participating medium: scatters into a uniformly
+/// random direction.
+pub struct Isotropic {
+    pub albedo: Arc<dyn Texture>
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color3) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord, _sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        let attenuation = self.albedo.value(hit_record.u, hit_record.v, &hit_record.p);
+
+        Some(ScatterRecord::Diffuse { attenuation, pdf: Box::new(SpherePdf) })
+    }
+
+    fn scattering_pdf(&self, _ray_in: &Ray, _hit_record: &HitRecord, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * PI)
     }
 }
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use crate::{
+    Point3,
+    aabb::Aabb,
+    hittable::{Hit, HitRecord},
+    material::Material,
+    ray::Ray,
+    sampler::Sampler,
+    sphere::hit_sphere,
+    vec3::Vec3,
+};
+
+/// Sphere moving in a straight line from `center0` at `time0` to `center1`
+/// at `time1`. It rests at the nearest keyframe outside that interval.
+pub struct MovingSphere {
+    pub center0: Point3,
+    pub center1: Point3,
+    pub time0: f64,
+    pub time1: f64,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn new(
+            center0: Point3,
+            center1: Point3,
+            time0: f64,
+            time1: f64,
+            radius: f64,
+            material: Arc<dyn Material>
+        ) -> Self {
+        Self { center0, center1, time0, time1, radius, material }
+    }
+
+    pub fn center(&self, time: f64) -> Point3 {
+        if self.time1 <= self.time0 {
+            return self.center0;
+        }
+
+        let fraction = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+
+        self.center0 + (self.center1 - self.center0) * fraction
+    }
+}
+
+impl Hit for MovingSphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, _sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        hit_sphere(self.center(ray.time()), self.radius, &self.material, ray, t_min, t_max)
+    }
+
+    /// Covers the sphere along its whole path.
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius.abs(), self.radius.abs(), self.radius.abs());
+        let box0 = Aabb::new(self.center0 - r, self.center0 + r);
+        let box1 = Aabb::new(self.center1 - r, self.center1 + r);
+
+        Some(Aabb::surrounding_box(&box0, &box1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color3, material::Lambertian, sampler::IndependentSampler};
+
+    fn moving(radius: f64) -> MovingSphere {
+        let material = Arc::new(Lambertian::new(Color3::new(0.5, 0.5, 0.5)));
+
+        MovingSphere::new(Point3::new(0.0, 0.0, 0.0), Point3::new(4.0, -2.0, 1.0), 1.0, 3.0, radius, material)
+    }
+
+    fn assert_at(actual: Point3, expected: [f64; 3]) {
+        let expected = Point3::new(expected[0], expected[1], expected[2]);
+        assert!((actual - expected).length() < 1e-12, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn center_is_interpolated_between_keyframes() {
+        let sphere = moving(1.0);
+
+        assert_at(sphere.center(1.0), [0.0, 0.0, 0.0]);
+        assert_at(sphere.center(3.0), [4.0, -2.0, 1.0]);
+        assert_at(sphere.center(2.0), [2.0, -1.0, 0.5]);
+        assert_at(sphere.center(1.5), [1.0, -0.5, 0.25]);
+        // Outside the interval the sphere rests at the nearest keyframe.
+        assert_at(sphere.center(-5.0), [0.0, 0.0, 0.0]);
+        assert_at(sphere.center(10.0), [4.0, -2.0, 1.0]);
+    }
+
+    #[test]
+    fn empty_intervals_stay_at_the_first_center() {
+        let mut sphere = moving(1.0);
+        sphere.time1 = sphere.time0;
+
+        assert_at(sphere.center(1.0), [0.0, 0.0, 0.0]);
+        assert_at(sphere.center(3.0), [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn hits_follow_the_ray_time() {
+        let sphere = moving(1.0);
+        let mut sampler = IndependentSampler::new(0);
+        let down_at = |x, y, time| Ray::with_time(Point3::new(x, y, 10.0), Vec3::new(0.0, 0.0, -1.0), time);
+
+        let rec = sphere.hit(&down_at(0.0, 0.0, 1.0), 0.001, f64::INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 9.0).abs() < 1e-12);
+        assert!(sphere.hit(&down_at(0.0, 0.0, 3.0), 0.001, f64::INFINITY, &mut sampler).is_none());
+
+        let rec = sphere.hit(&down_at(4.0, -2.0, 3.0), 0.001, f64::INFINITY, &mut sampler).unwrap();
+        assert!((rec.t - 8.0).abs() < 1e-12);
+        assert!(sphere.hit(&down_at(4.0, -2.0, 1.0), 0.001, f64::INFINITY, &mut sampler).is_none());
+    }
+
+    #[test]
+    fn bounding_box_covers_the_whole_path() {
+        for radius in [0.5, -0.5] {
+            let sphere = moving(radius);
+            let bbox = sphere.bounding_box().unwrap();
+
+            assert_at(bbox.minimum, [-0.5, -2.5, -0.5]);
+            assert_at(bbox.maximum, [4.5, 0.5, 1.5]);
+
+            for i in 0..=20 {
+                let center = sphere.center(1.0 + 2.0 * i as f64 / 20.0);
+                for axis in 0..3 {
+                    assert!(bbox.minimum[axis] <= center[axis] - 0.5);
+                    assert!(bbox.maximum[axis] >= center[axis] + 0.5);
+                }
+            }
+        }
+    }
+}
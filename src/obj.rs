@@ -0,0 +1,519 @@
+//! Wavefront OBJ and MTL import.
+//!
+//! Faces are grouped into one [`TriangleMesh`] per group (`g`/`o`) and
+//! material (`usemtl`). Polygons are fan-triangulated, so they are expected
+//! to be convex.
+//!
+//! MTL materials are mapped onto the renderer's materials:
+//! - `d` < 1 (or `Tr` > 0, or `illum` 4, 6, 7 or 9) gives a [`Dielectric`]
+//!   with index of refraction `Ni` (1.5 if missing);
+//! - `illum` 3, or a `Ks` brighter than `Kd`, gives a [`Metal`] tinted by
+//!   `Ks`, whose fuzz shrinks as the specular exponent `Ns` grows;
+//! - everything else is a [`Lambertian`] with albedo `Kd`.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    fs,
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{
+    Color3,
+    Point3,
+    hittable_list::HittableList,
+    material::{Dielectric, Lambertian, Material, Metal},
+    triangle::{MeshData, TriangleMesh},
+    vec3::Vec3,
+};
+
+/// A problem in an OBJ or MTL file.
+#[derive(Debug)]
+pub struct ObjError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.file, self.line, self.message)
+        }
+    }
+}
+
+impl Error for ObjError {}
+
+/// Tracks where we are in a file so errors can point at it.
+struct Location<'a> {
+    file: &'a str,
+    line: usize,
+}
+
+impl Location<'_> {
+    fn error(&self, message: impl Into<String>) -> ObjError {
+        ObjError {
+            file: self.file.to_string(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn number(&self, keyword: &str, token: Option<&str>) -> Result<f64, ObjError> {
+        let token = token.ok_or_else(|| self.error(format!("`{}` is missing a value", keyword)))?;
+
+        match token.parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(n),
+            _ => Err(self.error(format!("invalid number `{}` in `{}`", token, keyword))),
+        }
+    }
+
+    fn vec3<'t>(&self, keyword: &str, tokens: &mut impl Iterator<Item = &'t str>) -> Result<Vec3, ObjError> {
+        Ok(Vec3::new(
+            self.number(keyword, tokens.next())?,
+            self.number(keyword, tokens.next())?,
+            self.number(keyword, tokens.next())?,
+        ))
+    }
+}
+
+/// A mesh built from one group/material combination of an OBJ file.
+pub struct ObjMesh {
+    pub group: String,
+    pub material: Option<String>,
+    pub mesh: TriangleMesh,
+}
+
+/// Reads the materials of an MTL file.
+pub fn parse_mtl(source: &str, file: &str) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    #[derive(Default)]
+    struct MtlEntry {
+        name: String,
+        line: usize,
+        kd: Option<Color3>,
+        ks: Option<Color3>,
+        ns: Option<f64>,
+        ni: Option<f64>,
+        d: Option<f64>,
+        illum: Option<i32>,
+    }
+
+    let mut entries: Vec<MtlEntry> = Vec::new();
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let loc = Location { file, line: index + 1 };
+        let line = raw_line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            let name = tokens.collect::<Vec<_>>().join(" ");
+            if name.is_empty() {
+                return Err(loc.error("`newmtl` needs a material name"));
+            }
+            entries.push(MtlEntry { name, line: loc.line, ..Default::default() });
+            continue;
+        }
+
+        let entry = match entries.last_mut() {
+            Some(entry) => entry,
+            None => return Err(loc.error(format!("`{}` appears before any `newmtl`", keyword))),
+        };
+
+        match keyword {
+            "Kd" | "Ks" => {
+                let first = tokens.next();
+                if matches!(first, Some("spectral") | Some("xyz")) {
+                    return Err(loc.error(format!("`{} {}` colors are not supported", keyword, first.unwrap())));
+                }
+                let r = loc.number(keyword, first)?;
+                // A single value means a gray color.
+                let color = match tokens.next() {
+                    Some(g) => Color3::new(r, loc.number(keyword, Some(g))?, loc.number(keyword, tokens.next())?),
+                    None => Color3::new(r, r, r),
+                };
+                if keyword == "Kd" {
+                    entry.kd = Some(color);
+                } else {
+                    entry.ks = Some(color);
+                }
+            }
+            "Ns" => entry.ns = Some(loc.number(keyword, tokens.next())?),
+            "Ni" => entry.ni = Some(loc.number(keyword, tokens.next())?),
+            "d" => entry.d = Some(loc.number(keyword, tokens.next())?),
+            "Tr" => entry.d = Some(1.0 - loc.number(keyword, tokens.next())?),
+            "illum" => {
+                let n = loc.number(keyword, tokens.next())?;
+                entry.illum = Some(n as i32);
+            }
+            // Ambient/emissive colors, texture maps and the like have no
+            // counterpart in our materials.
+            _ => {}
+        }
+    }
+
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+
+    for entry in entries {
+        let kd = entry.kd.unwrap_or_else(|| Color3::new(0.8, 0.8, 0.8));
+        let ks = entry.ks.unwrap_or_else(|| Color3::new(0.0, 0.0, 0.0));
+        let max = |c: Color3| c.x().max(c.y()).max(c.z());
+        let transparent = entry.d.is_some_and(|d| d < 1.0) || matches!(entry.illum, Some(4 | 6 | 7 | 9));
+
+        let material: Arc<dyn Material> = if transparent {
+            let ni = entry.ni.unwrap_or(1.5);
+            if ni <= 0.0 {
+                let loc = Location { file, line: entry.line };
+                return Err(loc.error(format!("material `{}` has a non-positive `Ni`", entry.name)));
+            }
+            Arc::new(Dielectric::new(ni))
+        } else if entry.illum == Some(3) || max(ks) > max(kd) {
+            // Map the Phong exponent to a roughness: sharp highlights, little fuzz.
+            let ns = entry.ns.unwrap_or(0.0).max(0.0);
+            let fuzz = (2.0 / (ns + 2.0)).sqrt();
+            Arc::new(Metal::new(ks, fuzz))
+        } else {
+            Arc::new(Lambertian::new(kd))
+        };
+
+        materials.insert(entry.name, material);
+    }
+
+    Ok(materials)
+}
+
+/// One face corner: indices into the position, texture and normal arrays.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Corner {
+    v: usize,
+    vt: Option<usize>,
+    vn: Option<usize>,
+}
+
+#[derive(Default)]
+struct MeshBuilder {
+    corners: Vec<Corner>,
+    lookup: HashMap<Corner, usize>,
+    indices: Vec<[usize; 3]>,
+}
+
+impl MeshBuilder {
+    fn vertex(&mut self, corner: Corner) -> usize {
+        if let Some(&index) = self.lookup.get(&corner) {
+            return index;
+        }
+        let index = self.corners.len();
+        self.corners.push(corner);
+        self.lookup.insert(corner, index);
+        index
+    }
+}
+
+/// Resolves a 1-based (or negative, relative) OBJ index.
+fn resolve_index(loc: &Location, token: &str, count: usize, what: &str) -> Result<usize, ObjError> {
+    let index: i64 = token.parse()
+        .map_err(|_| loc.error(format!("invalid {} index `{}`", what, token)))?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else if index < 0 {
+        count as i64 + index
+    } else {
+        return Err(loc.error(format!("{} indices start at 1, found 0", what)));
+    };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(loc.error(format!(
+            "{} index {} is out of range (only {} defined so far)", what, index, count
+        )));
+    }
+
+    Ok(resolved as usize)
+}
+
+/// Resolves the argument of a `mtllib` statement to the library's file name
+/// (for error messages) and source text.
+pub type MtlLoader<'a> = dyn FnMut(&str) -> Result<(String, String), ObjError> + 'a;
+
+/// Parses an OBJ file, calling `load_mtl` for every `mtllib` statement.
+pub fn parse_obj(
+        source: &str,
+        file: &str,
+        default_material: Arc<dyn Material>,
+        load_mtl: &mut MtlLoader
+    ) -> Result<Vec<ObjMesh>, ObjError> {
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<[f64; 2]> = Vec::new();
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+
+    let mut group = String::from("default");
+    let mut current_material: Option<String> = None;
+    // Meshes in first-use order, keyed by group and material.
+    let mut builders: Vec<((String, Option<String>), MeshBuilder)> = Vec::new();
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let loc = Location { file, line: index + 1 };
+        let line = raw_line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        match keyword {
+            "v" => positions.push(loc.vec3(keyword, &mut tokens)?),
+            "vn" => normals.push(loc.vec3(keyword, &mut tokens)?),
+            "vt" => {
+                let u = loc.number(keyword, tokens.next())?;
+                let v = match tokens.next() {
+                    Some(token) => loc.number(keyword, Some(token))?,
+                    None => 0.0,
+                };
+                uvs.push([u, v]);
+            }
+            "f" => {
+                let mut corners = Vec::new();
+                for token in tokens {
+                    let mut parts = token.split('/');
+                    let v = resolve_index(&loc, parts.next().unwrap_or(""), positions.len(), "vertex")?;
+                    let vt = match parts.next() {
+                        Some("") | None => None,
+                        Some(t) => Some(resolve_index(&loc, t, uvs.len(), "texture")?),
+                    };
+                    let vn = match parts.next() {
+                        Some("") | None => None,
+                        Some(t) => Some(resolve_index(&loc, t, normals.len(), "normal")?),
+                    };
+                    if parts.next().is_some() {
+                        return Err(loc.error(format!("malformed face vertex `{}`", token)));
+                    }
+                    corners.push(Corner { v, vt, vn });
+                }
+
+                if corners.len() < 3 {
+                    return Err(loc.error(format!("a face needs at least 3 vertices, found {}", corners.len())));
+                }
+
+                let key = (group.clone(), current_material.clone());
+                let builder = match builders.iter().position(|(k, _)| *k == key) {
+                    Some(i) => &mut builders[i].1,
+                    None => {
+                        builders.push((key, MeshBuilder::default()));
+                        &mut builders.last_mut().unwrap().1
+                    }
+                };
+
+                let first = builder.vertex(corners[0]);
+                for pair in corners[1..].windows(2) {
+                    let b = builder.vertex(pair[0]);
+                    let c = builder.vertex(pair[1]);
+                    builder.indices.push([first, b, c]);
+                }
+            }
+            "g" | "o" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                group = if name.is_empty() { String::from("default") } else { name };
+            }
+            "usemtl" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                if !materials.contains_key(&name) {
+                    return Err(loc.error(format!("unknown material `{}` (missing from the mtllib files)", name)));
+                }
+                current_material = Some(name);
+            }
+            "mtllib" => {
+                for library in tokens {
+                    let (mtl_file, mtl_source) = load_mtl(library)
+                        .map_err(|err| if err.line == 0 { loc.error(err.message) } else { err })?;
+                    materials.extend(parse_mtl(&mtl_source, &mtl_file)?);
+                }
+            }
+            // Smoothing groups, lines, points and free-form geometry are ignored.
+            _ => {}
+        }
+    }
+
+    let meshes = builders.into_iter().map(|((group, material_name), builder)| {
+        let material = match &material_name {
+            Some(name) => Arc::clone(&materials[name]),
+            None => Arc::clone(&default_material),
+        };
+
+        // Normals and UVs are only kept when every vertex of the mesh has them.
+        let has_normals = builder.corners.iter().all(|c| c.vn.is_some());
+        let has_uvs = builder.corners.iter().all(|c| c.vt.is_some());
+
+        let data = MeshData {
+            positions: builder.corners.iter().map(|c| positions[c.v]).collect(),
+            normals: if has_normals {
+                builder.corners.iter().filter_map(|c| c.vn).map(|i| normals[i]).collect()
+            } else {
+                Vec::new()
+            },
+            uvs: if has_uvs {
+                builder.corners.iter().filter_map(|c| c.vt).map(|i| uvs[i]).collect()
+            } else {
+                Vec::new()
+            },
+            indices: builder.indices,
+            material,
+        };
+
+        ObjMesh {
+            group,
+            material: material_name,
+            mesh: TriangleMesh::new(data),
+        }
+    }).collect();
+
+    Ok(meshes)
+}
+
+/// Loads an OBJ file and the MTL libraries it references (relative to the
+/// OBJ file). Faces without a material use `default_material`.
+pub fn load<P: AsRef<Path>>(path: P, default_material: Arc<dyn Material>) -> Result<Vec<ObjMesh>, ObjError> {
+    let path = path.as_ref();
+    let file = path.display().to_string();
+    let source = fs::read_to_string(path).map_err(|err| ObjError {
+        file: file.clone(),
+        line: 0,
+        message: format!("cannot read file: {}", err),
+    })?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut load_mtl = |name: &str| {
+        let mtl_path = base_dir.join(name);
+        let mtl_file = mtl_path.display().to_string();
+
+        match fs::read_to_string(&mtl_path) {
+            Ok(source) => Ok((mtl_file, source)),
+            Err(err) => Err(ObjError {
+                file: file.clone(),
+                line: 0,
+                message: format!("cannot read material library {}: {}", mtl_file, err),
+            }),
+        }
+    };
+
+    parse_obj(&source, &file, default_material, &mut load_mtl)
+}
+
+/// Loads an OBJ file into a list ready to be added to the world.
+pub fn load_into_list<P: AsRef<Path>>(path: P, default_material: Arc<dyn Material>) -> Result<HittableList, ObjError> {
+    let mut list = HittableList::new();
+
+    for obj_mesh in load(path, default_material)? {
+        list.add(Arc::new(obj_mesh.mesh));
+    }
+
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+    const MTL: &str = "newmtl red\nKd 1 0 0\n";
+
+    fn parse(source: &str) -> Result<Vec<ObjMesh>, ObjError> {
+        let default_material = Arc::new(Lambertian::new(Color3::new(0.5, 0.5, 0.5)));
+        let mut load_mtl = |name: &str| Ok((name.to_string(), MTL.to_string()));
+
+        parse_obj(source, "test.obj", default_material, &mut load_mtl)
+    }
+
+    fn obj_error(source: &str) -> String {
+        match parse(source) {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    fn mtl_error(source: &str) -> String {
+        match parse_mtl(source, "test.mtl") {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_faces_with_relative_indices_and_materials() {
+        let meshes = parse(&format!("mtllib lib.mtl\n{}usemtl red\nf 1 2 3\nf -4 -2 -1\n", SQUARE)).unwrap();
+
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].material.as_deref(), Some("red"));
+        assert_eq!(meshes[0].mesh.data().indices.len(), 2);
+    }
+
+    #[test]
+    fn quads_are_fanned_into_triangles() {
+        let meshes = parse(&format!("{}f 1 2 3 4\n", SQUARE)).unwrap();
+
+        assert_eq!(meshes[0].mesh.data().indices, [[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn rejects_a_zero_index() {
+        assert_eq!(obj_error(&format!("{}f 0 1 2\n", SQUARE)), "test.obj:5: vertex indices start at 1, found 0");
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        assert_eq!(
+            obj_error(&format!("{}f 1 2 5\n", SQUARE)),
+            "test.obj:5: vertex index 5 is out of range (only 4 defined so far)"
+        );
+        assert_eq!(
+            obj_error(&format!("{}f -1 -2 -5\n", SQUARE)),
+            "test.obj:5: vertex index -5 is out of range (only 4 defined so far)"
+        );
+        // Only vertices defined before the face count.
+        assert_eq!(
+            obj_error("v 0 0 0\nv 1 0 0\nf 1 2 3\nv 0 1 0\n"),
+            "test.obj:3: vertex index 3 is out of range (only 2 defined so far)"
+        );
+        assert_eq!(
+            obj_error(&format!("{}vn 0 0 1\nf 1//1 2//1 3//2\n", SQUARE)),
+            "test.obj:6: normal index 2 is out of range (only 1 defined so far)"
+        );
+        assert_eq!(obj_error(&format!("{}f 1 2 x\n", SQUARE)), "test.obj:5: invalid vertex index `x`");
+    }
+
+    #[test]
+    fn rejects_an_unknown_material() {
+        assert_eq!(
+            obj_error(&format!("mtllib lib.mtl\n{}usemtl blue\nf 1 2 3\n", SQUARE)),
+            "test.obj:6: unknown material `blue` (missing from the mtllib files)"
+        );
+        assert_eq!(
+            obj_error("usemtl red\n"),
+            "test.obj:1: unknown material `red` (missing from the mtllib files)"
+        );
+    }
+
+    #[test]
+    fn rejects_faces_with_fewer_than_three_vertices() {
+        assert_eq!(
+            obj_error(&format!("{}f 1 2\n", SQUARE)),
+            "test.obj:5: a face needs at least 3 vertices, found 2"
+        );
+        assert_eq!(obj_error(&format!("{}f\n", SQUARE)), "test.obj:5: a face needs at least 3 vertices, found 0");
+    }
+
+    #[test]
+    fn rejects_properties_before_newmtl() {
+        assert_eq!(mtl_error("# header\nKd 1 0 0\nnewmtl red\n"), "test.mtl:2: `Kd` appears before any `newmtl`");
+        assert_eq!(mtl_error("newmtl\n"), "test.mtl:1: `newmtl` needs a material name");
+        assert!(parse_mtl(MTL, "test.mtl").unwrap().contains_key("red"));
+    }
+}
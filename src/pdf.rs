@@ -0,0 +1,225 @@
+use std::f64::consts::PI;
+
+use crate::{Point3, hittable::Hit, sampler::Sampler, vec3::Vec3};
+
+/// Orthonormal basis built around a direction, which becomes its `w` axis.
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn new(w: &Vec3) -> Self {
+        let w = Vec3::unit_vector(*w);
+        // Any vector not parallel to w works as a helper.
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = Vec3::unit_vector(Vec3::cross(&w, &a));
+        let u = Vec3::cross(&w, &v);
+
+        Self { u, v, w }
+    }
+
+    /// Converts coordinates in this basis to world space.
+    pub fn local(&self, a: &Vec3) -> Vec3 {
+        self.u * a.x() + self.v * a.y() + self.w * a.z()
+    }
+}
+
+/// Probability density over directions, which can also be sampled.
+pub trait Pdf {
+    /// Density of `direction`, per unit solid angle.
+    fn value(&self, direction: &Vec3) -> f64;
+
+    /// Random direction, or `None` if the density has nothing to sample.
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3>;
+}
+
+/// Cosine-weighted hemisphere around a surface normal, the ideal density for
+/// Lambertian surfaces.
+pub struct CosinePdf {
+    uvw: Onb,
+}
+
+impl CosinePdf {
+    pub fn new(normal: &Vec3) -> Self {
+        Self { uvw: Onb::new(normal) }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let cosine = Vec3::dot(&Vec3::unit_vector(*direction), &self.uvw.w);
+
+        (cosine / PI).max(0.0)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        Some(self.uvw.local(&Vec3::random_cosine_direction(sampler)))
+    }
+}
+
+/// All directions equally likely.
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        Some(Vec3::random_unit_vector(sampler))
+    }
+}
+
+/// Directions from `origin` towards the objects, usually the scene's lights,
+/// as sampled by [`Hit::random`].
+pub struct HittablePdf<'a> {
+    objects: &'a dyn Hit,
+    origin: Point3,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(objects: &'a dyn Hit, origin: Point3) -> Self {
+        Self { objects, origin }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.objects.pdf_value(&self.origin, direction)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        self.objects.random(&self.origin, sampler)
+    }
+}
+
+/// Even mix of two densities: samples either one with equal probability.
+pub struct MixturePdf<'a> {
+    pdfs: [&'a dyn Pdf; 2],
+}
+
+impl<'a> MixturePdf<'a> {
+    pub fn new(p0: &'a dyn Pdf, p1: &'a dyn Pdf) -> Self {
+        Self { pdfs: [p0, p1] }
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        0.5 * self.pdfs[0].value(direction) + 0.5 * self.pdfs[1].value(direction)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        if sampler.random() < 0.5 {
+            self.pdfs[0].generate(sampler)
+        } else {
+            self.pdfs[1].generate(sampler)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        Color3,
+        hittable_list::HittableList,
+        material::DiffuseLight,
+        moving_sphere::MovingSphere,
+        quad::{Cuboid, Quad},
+        sampler::IndependentSampler,
+        sphere::Sphere,
+        triangle::Triangle,
+    };
+
+    fn lights() -> Vec<(&'static str, Arc<dyn Hit>)> {
+        let lamp = Arc::new(DiffuseLight::new(Color3::new(1.0, 1.0, 1.0)));
+        let point = Point3::new;
+
+        vec![
+            ("sphere", Arc::new(Sphere::new(point(0.0, 0.0, 2.0), 1.0, lamp.clone()))),
+            ("quad", Arc::new(Quad::new(point(-1.0, -1.0, 1.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), lamp.clone()))),
+            ("triangle", Arc::new(Triangle::new(point(-1.0, -1.0, 1.0), point(2.0, -1.0, 1.5), point(-1.0, 2.0, 1.0), lamp.clone()))),
+            ("box", Arc::new(Cuboid::new(point(-1.0, -1.0, 1.0), point(1.0, 1.0, 2.0), lamp))),
+        ]
+    }
+
+    #[test]
+    fn light_densities_integrate_to_one() {
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let mut sampler = IndependentSampler::new(5);
+        let n = 400_000;
+
+        for (name, light) in lights() {
+            let sum: f64 = (0..n)
+                .map(|_| light.pdf_value(&origin, &Vec3::random_unit_vector(&mut sampler)))
+                .sum();
+            let integral = sum * 4.0 * PI / n as f64;
+
+            assert!((integral - 1.0).abs() < 0.03, "{}: {}", name, integral);
+        }
+    }
+
+    #[test]
+    fn sampled_directions_have_a_density() {
+        let origin = Point3::new(0.2, -0.1, 0.0);
+        let mut sampler = IndependentSampler::new(9);
+
+        for (name, light) in lights() {
+            let pdf = HittablePdf::new(light.as_ref(), origin);
+            for _ in 0..1000 {
+                let direction = pdf.generate(&mut sampler).unwrap();
+                assert!(pdf.value(&direction) > 0.0, "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn objects_without_sampling_generate_nothing() {
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let mut sampler = IndependentSampler::new(0);
+        let lamp = Arc::new(DiffuseLight::new(Color3::new(1.0, 1.0, 1.0)));
+        let moving = MovingSphere::new(Point3::new(0.0, 0.0, 2.0), Point3::new(0.0, 1.0, 2.0), 0.0, 1.0, 1.0, lamp);
+
+        assert!(moving.random(&origin, &mut sampler).is_none());
+        assert!(HittablePdf::new(&HittableList::new(), origin).generate(&mut sampler).is_none());
+    }
+
+    #[test]
+    fn mixture_density_is_the_average_of_its_parts() {
+        let cosine = CosinePdf::new(&Vec3::new(0.0, 0.0, 1.0));
+        let mixture = MixturePdf::new(&cosine, &SpherePdf);
+        let mut sampler = IndependentSampler::new(4);
+
+        for _ in 0..100 {
+            let direction = Vec3::random_unit_vector(&mut sampler);
+            let expected = 0.5 * cosine.value(&direction) + 0.5 / (4.0 * PI);
+            assert!((mixture.value(&direction) - expected).abs() < 1e-15);
+        }
+
+        // Only the sphere half reaches below the horizon, half of the time.
+        let n = 100_000;
+        let below = (0..n).filter(|_| mixture.generate(&mut sampler).unwrap().z() < 0.0).count();
+        assert!((below as f64 / n as f64 - 0.25).abs() < 0.01, "{}", below);
+    }
+
+    #[test]
+    fn mixture_passes_on_failed_samples() {
+        let lights = HittableList::new();
+        let empty = HittablePdf::new(&lights, Point3::new(0.0, 0.0, 0.0));
+        let mixture = MixturePdf::new(&SpherePdf, &empty);
+        let mut sampler = IndependentSampler::new(6);
+
+        let generated: Vec<bool> = (0..1000).map(|_| mixture.generate(&mut sampler).is_some()).collect();
+        assert!(generated.contains(&true) && generated.contains(&false));
+        assert_eq!(mixture.value(&Vec3::new(0.0, 1.0, 0.0)), 0.5 / (4.0 * PI));
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{Point3, sampler::Sampler, vec3::Vec3};
+
+const POINT_COUNT: usize = 256;
+
+/// Gradient (Perlin) noise over 3D space.
+///
+/// The gradients and permutations are drawn from the given sampler, so the
+/// same seed always produces the same noise.
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(sampler: &mut dyn Sampler) -> Self {
+        let gradients = (0..POINT_COUNT)
+            .map(|_| Vec3::unit_vector(Vec3::random_vec3_in_range(sampler, -1.0, 1.0)))
+            .collect();
+
+        Self {
+            gradients,
+            perm_x: Self::generate_perm(sampler),
+            perm_y: Self::generate_perm(sampler),
+            perm_z: Self::generate_perm(sampler),
+        }
+    }
+
+    /// Shuffled `0..POINT_COUNT` (Fisher-Yates).
+    fn generate_perm(sampler: &mut dyn Sampler) -> Vec<usize> {
+        let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+
+        for i in (1..POINT_COUNT).rev() {
+            let target = ((sampler.random() * (i + 1) as f64) as usize).min(i);
+            perm.swap(i, target);
+        }
+
+        perm
+    }
+
+    /// Noise value at `p`, roughly in `[-1, 1]`.
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let (fx, fy, fz) = (p.x().floor(), p.y().floor(), p.z().floor());
+        let (u, v, w) = (p.x() - fx, p.y() - fy, p.z() - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        let mut corners = [[[Vec3::new(0.0, 0.0, 0.0); 2]; 2]; 2];
+        for (di, plane) in corners.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, gradient) in row.iter_mut().enumerate() {
+                    let index = self.perm_x[Self::wrap(i + di as i64)]
+                        ^ self.perm_y[Self::wrap(j + dj as i64)]
+                        ^ self.perm_z[Self::wrap(k + dk as i64)];
+                    *gradient = self.gradients[index];
+                }
+            }
+        }
+
+        Self::interpolate(&corners, u, v, w)
+    }
+
+    /// Sum of `depth` octaves of noise, each at double the frequency and half
+    /// the weight of the previous one.
+    pub fn turbulence(&self, p: &Point3, depth: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(&temp_p);
+            weight *= 0.5;
+            temp_p = temp_p * 2.0;
+        }
+
+        accum.abs()
+    }
+
+    fn wrap(i: i64) -> usize {
+        i.rem_euclid(POINT_COUNT as i64) as usize
+    }
+
+    /// Trilinear interpolation of the corner gradients' contributions, with
+    /// Hermite smoothing of the weights to hide the lattice.
+    fn interpolate(corners: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+        let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+        let (uu, vv, ww) = (smooth(u), smooth(v), smooth(w));
+        let mut accum = 0.0;
+
+        for (i, plane) in corners.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, gradient) in row.iter().enumerate() {
+                    let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                    let weight = Vec3::new(u - fi, v - fj, w - fk);
+
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * Vec3::dot(gradient, &weight);
+                }
+            }
+        }
+
+        accum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::IndependentSampler;
+
+    fn perlin(seed: u64) -> Perlin {
+        Perlin::new(&mut IndependentSampler::new(seed))
+    }
+
+    fn points(count: usize) -> Vec<Point3> {
+        let mut sampler = IndependentSampler::new(99);
+        (0..count).map(|_| Vec3::random_vec3_in_range(&mut sampler, -300.0, 300.0)).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_noise() {
+        let (a, b, other) = (perlin(7), perlin(7), perlin(8));
+        let points = points(200);
+
+        for p in &points {
+            assert_eq!(a.noise(p), b.noise(p));
+            assert_eq!(a.turbulence(p, 7), b.turbulence(p, 7));
+        }
+        assert!(points.iter().any(|p| a.noise(p) != other.noise(p)));
+    }
+
+    #[test]
+    fn noise_stays_in_range_and_varies() {
+        let noise = perlin(1);
+        let values: Vec<f64> = points(5000).iter().map(|p| noise.noise(p)).collect();
+
+        assert!(values.iter().all(|n| (-1.0..=1.0).contains(n)));
+        assert!(values.iter().any(|&n| n > 0.3) && values.iter().any(|&n| n < -0.3));
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        assert!(mean.abs() < 0.05, "mean {}", mean);
+    }
+
+    #[test]
+    fn noise_vanishes_on_the_lattice() {
+        let noise = perlin(2);
+
+        for (x, y, z) in [(0.0, 0.0, 0.0), (3.0, -7.0, 12.0), (-256.0, 255.0, 1000.0)] {
+            assert_eq!(noise.noise(&Point3::new(x, y, z)), 0.0);
+        }
+    }
+
+    #[test]
+    fn turbulence_sums_weighted_octaves() {
+        let noise = perlin(3);
+
+        for p in points(500) {
+            assert_eq!(noise.turbulence(&p, 0), 0.0);
+            assert_eq!(noise.turbulence(&p, 1), noise.noise(&p).abs());
+
+            let expected = noise.noise(&p) + 0.5 * noise.noise(&(p * 2.0)) + 0.25 * noise.noise(&(p * 4.0));
+            assert!((noise.turbulence(&p, 3) - expected.abs()).abs() < 1e-12);
+
+            // The octave weights sum to less than 2.
+            let t = noise.turbulence(&p, 7);
+            assert!((0.0..2.0).contains(&t), "{}", t);
+        }
+    }
+}
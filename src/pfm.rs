@@ -0,0 +1,47 @@
+//! Portable float map (PFM) encoder for linear HDR images.
+
+use std::io::{self, Write};
+
+/// Writes `rgb` (row-major, top row first) as a little-endian color PFM.
+///
+/// PFM stores scanlines bottom to top, so the rows are flipped on the way out.
+pub fn write_pfm<W: Write>(out: &mut W, width: u32, height: u32, rgb: &[[f32; 3]]) -> io::Result<()> {
+    let width = width as usize;
+    assert_eq!(rgb.len(), width * height as usize, "pixel buffer does not match image size");
+
+    // A negative scale marks little-endian data.
+    write!(out, "PF\n{} {}\n-1.0\n", width, height)?;
+
+    let mut bytes = Vec::with_capacity(rgb.len() * 12);
+    for row in rgb.chunks(width.max(1)).rev() {
+        for pixel in row {
+            for channel in pixel {
+                bytes.extend_from_slice(&channel.to_le_bytes());
+            }
+        }
+    }
+
+    out.write_all(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_and_bottom_up_rows() {
+        let rgb = [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0], [10.0, 11.0, 12.0]];
+        let mut out = Vec::new();
+        write_pfm(&mut out, 2, 2, &rgb).unwrap();
+
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&out[..header.len()], header);
+
+        let values: Vec<f32> = out[header.len()..]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        // The bottom row is stored first.
+        assert_eq!(values, [7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    }
+}
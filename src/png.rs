@@ -0,0 +1,335 @@
+//! PNG encoder for 8-bit RGB images, and a decoder for reading textures.
+
+use std::io::{self, Write};
+
+use crate::zlib;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+
+    table
+}
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let table = crc32_table();
+    let mut crc = 0xFFFF_FFFFu32;
+
+    for &byte in bytes {
+        crc = table[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+
+    crc ^ 0xFFFF_FFFF
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    // The CRC covers the chunk type and data, but not the length.
+    let mut crc_input = Vec::with_capacity(data.len() + 4);
+    crc_input.extend_from_slice(kind);
+    crc_input.extend_from_slice(data);
+
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(&crc_input)?;
+    out.write_all(&crc32(&crc_input).to_be_bytes())
+}
+
+/// Writes `rgb` (row-major, top row first, 3 bytes per pixel) as an 8-bit
+/// truecolor PNG tagged as sRGB.
+pub fn write_png<W: Write>(out: &mut W, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    let stride = width as usize * 3;
+    assert_eq!(rgb.len(), stride * height as usize, "pixel buffer does not match image size");
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, color type 2 (RGB), deflate, adaptive filtering, no interlace.
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    // Every scanline is prefixed with its filter type; 0 means unfiltered.
+    let mut raw = Vec::with_capacity((stride + 1) * height as usize);
+    for row in rgb.chunks(stride.max(1)) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    out.write_all(&SIGNATURE)?;
+    write_chunk(out, b"IHDR", &ihdr)?;
+    // Rendering intent 0 (perceptual).
+    write_chunk(out, b"sRGB", &[0])?;
+    write_chunk(out, b"IDAT", &zlib::compress(&raw))?;
+    write_chunk(out, b"IEND", &[])
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid PNG: {}", message.into()))
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Reverses the per-scanline filters in place, leaving only the pixel bytes.
+/// `raw` must hold `(stride + 1) * height` bytes.
+fn unfilter(raw: &[u8], stride: usize, height: usize, bpp: usize) -> io::Result<Vec<u8>> {
+    if raw.len() != (stride + 1) * height {
+        return Err(invalid("image data does not match the image size"));
+    }
+
+    let mut pixels = vec![0u8; stride * height];
+
+    for y in 0..height {
+        let filter = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        let (done, rest) = pixels.split_at_mut(y * stride);
+        let prior = if y == 0 { None } else { Some(&done[(y - 1) * stride..]) };
+        let current = &mut rest[..stride];
+
+        for x in 0..stride {
+            let a = if x >= bpp { current[x - bpp] } else { 0 };
+            let b = prior.map_or(0, |p| p[x]);
+            let c = if x >= bpp { prior.map_or(0, |p| p[x - bpp]) } else { 0 };
+
+            current[x] = match filter {
+                0 => line[x],
+                1 => line[x].wrapping_add(a),
+                2 => line[x].wrapping_add(b),
+                3 => line[x].wrapping_add(((a as u16 + b as u16) / 2) as u8),
+                4 => line[x].wrapping_add(paeth(a, b, c)),
+                _ => return Err(invalid(format!("unknown filter type {}", filter))),
+            };
+        }
+    }
+
+    Ok(pixels)
+}
+
+/// Decodes a non-interlaced PNG of any color type into 8-bit RGB (row-major,
+/// top row first). Alpha is dropped and 16-bit samples are cut to 8 bits.
+pub fn read_png(data: &[u8]) -> io::Result<(u32, u32, Vec<u8>)> {
+    if !data.starts_with(&SIGNATURE) {
+        return Err(invalid("missing signature"));
+    }
+
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut compressed = Vec::new();
+    let mut pos = SIGNATURE.len();
+
+    loop {
+        if pos + 12 > data.len() {
+            return Err(invalid("truncated chunk"));
+        }
+        let len = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let end = pos + 8 + len;
+        if end + 4 > data.len() {
+            return Err(invalid("truncated chunk"));
+        }
+        let kind = &data[pos + 4..pos + 8];
+        let body = &data[pos + 8..end];
+        let crc = u32::from_be_bytes(data[end..end + 4].try_into().unwrap());
+        if crc != crc32(&data[pos + 4..end]) {
+            return Err(invalid(format!("bad CRC in {} chunk", String::from_utf8_lossy(kind))));
+        }
+        pos = end + 4;
+
+        match kind {
+            b"IHDR" if body.len() == 13 => header = Some(body),
+            b"PLTE" => palette = body,
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+
+    let header = header.ok_or_else(|| invalid("missing IHDR chunk"))?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    let (depth, color_type, interlace) = (header[8], header[9], header[12]);
+
+    if interlace != 0 {
+        return Err(invalid("interlaced images are not supported"));
+    }
+    let channels = match (color_type, depth) {
+        (0, 1 | 2 | 4 | 8 | 16) => 1,
+        (3, 1 | 2 | 4 | 8) => 1,
+        (4, 8 | 16) => 2,
+        (2, 8 | 16) => 3,
+        (6, 8 | 16) => 4,
+        _ => return Err(invalid(format!("bit depth {} with color type {}", depth, color_type))),
+    };
+
+    // The dimensions are untrusted, so the sizes derived from them are
+    // checked, and inflating stops once the expected data is exceeded.
+    let bits_per_pixel = channels * depth as usize;
+    let stride = (width as usize)
+        .checked_mul(bits_per_pixel)
+        .map(|bits| bits.div_ceil(8))
+        .ok_or_else(|| invalid("image dimensions are too large"))?;
+    let raw_len = (stride + 1)
+        .checked_mul(height as usize)
+        .ok_or_else(|| invalid("image dimensions are too large"))?;
+    let raw = zlib::decompress(&compressed, raw_len)?;
+    let pixels = unfilter(&raw, stride, height as usize, bits_per_pixel.div_ceil(8))?;
+
+    // Returns channel `c` of pixel `x` in scanline `row`, scaled to 8 bits
+    // unless it is a palette index.
+    let sample = |row: &[u8], x: usize, c: usize| -> u8 {
+        match depth {
+            16 => row[(x * channels + c) * 2],
+            8 => row[x * channels + c],
+            _ => {
+                let bit = x * depth as usize;
+                let max = (1u16 << depth) - 1;
+                let value = (row[bit / 8] >> (8 - depth as usize - bit % 8)) as u16 & max;
+                if color_type == 3 { value as u8 } else { (value * 255 / max) as u8 }
+            }
+        }
+    };
+
+    let mut rgb = Vec::with_capacity(width as usize * height as usize * 3);
+    for row in pixels.chunks(stride.max(1)).take(height as usize) {
+        for x in 0..width as usize {
+            match color_type {
+                0 | 4 => {
+                    let gray = sample(row, x, 0);
+                    rgb.extend_from_slice(&[gray, gray, gray]);
+                }
+                3 => {
+                    let i = sample(row, x, 0) as usize * 3;
+                    let entry = palette.get(i..i + 3).ok_or_else(|| invalid("palette index out of range"))?;
+                    rgb.extend_from_slice(entry);
+                }
+                _ => rgb.extend_from_slice(&[sample(row, x, 0), sample(row, x, 1), sample(row, x, 2)]),
+            }
+        }
+    }
+
+    Ok((width, height, rgb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_known_answers() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    }
+
+    fn sample_png() -> (Vec<u8>, Vec<u8>) {
+        let rgb: Vec<u8> = (0..5 * 3 * 3).map(|i| (i * 17 % 256) as u8).collect();
+        let mut out = Vec::new();
+        write_png(&mut out, 5, 3, &rgb).unwrap();
+
+        (out, rgb)
+    }
+
+    /// A PNG with the given IHDR fields and `raw` (already filtered) as its
+    /// image data.
+    fn custom_png(width: u32, height: u32, depth: u8, color_type: u8, raw: &[u8]) -> Vec<u8> {
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&width.to_be_bytes());
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        ihdr.extend_from_slice(&[depth, color_type, 0, 0, 0]);
+
+        let mut out = SIGNATURE.to_vec();
+        write_chunk(&mut out, b"IHDR", &ihdr).unwrap();
+        write_chunk(&mut out, b"IDAT", &zlib::compress(raw)).unwrap();
+        write_chunk(&mut out, b"IEND", &[]).unwrap();
+        out
+    }
+
+    fn png_error(data: &[u8]) -> String {
+        read_png(data).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn round_trips_through_write_png() {
+        let (png, rgb) = sample_png();
+
+        assert_eq!(read_png(&png).unwrap(), (5, 3, rgb));
+    }
+
+    #[test]
+    fn decodes_filtered_and_low_depth_scanlines() {
+        // Two 2x1 gray rows: Sub filter, then Up filter.
+        let raw = [1, 10, 5, 2, 1, 2];
+        assert_eq!(
+            read_png(&custom_png(2, 2, 8, 0, &raw)).unwrap().2,
+            [10, 10, 10, 15, 15, 15, 11, 11, 11, 17, 17, 17]
+        );
+
+        // 1-bit gray: 0b1010_0000 is white, black, white.
+        assert_eq!(read_png(&custom_png(3, 1, 1, 0, &[0, 0b1010_0000])).unwrap().2, [255, 255, 255, 0, 0, 0, 255, 255, 255]);
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let (png, _) = sample_png();
+
+        for len in 0..png.len() {
+            assert!(read_png(&png[..len]).is_err(), "truncated to {} bytes", len);
+        }
+        assert_eq!(png_error(&png[..png.len() - 13]), "invalid PNG: truncated chunk");
+    }
+
+    #[test]
+    fn rejects_a_bad_crc() {
+        let (mut png, _) = sample_png();
+        // The last byte of the IDAT data, just before its CRC and the IEND chunk.
+        let last_idat_byte = png.len() - 12 - 5;
+        png[last_idat_byte] ^= 0x40;
+
+        assert_eq!(png_error(&png), "invalid PNG: bad CRC in IDAT chunk");
+    }
+
+    #[test]
+    fn rejects_oversized_headers_without_allocating() {
+        for (width, height) in [(u32::MAX, u32::MAX), (u32::MAX, 1), (1 << 20, 1 << 20)] {
+            for (depth, color_type) in [(16, 6), (8, 2), (1, 0)] {
+                let png = custom_png(width, height, depth, color_type, &[0, 1, 2, 3]);
+                assert!(read_png(&png).is_err(), "{}x{}", width, height);
+            }
+        }
+
+        // Data that inflates past the size the header promises.
+        let png = custom_png(1, 1, 8, 2, &[0; 4096]);
+        assert_eq!(png_error(&png), "invalid zlib stream: output is longer than expected");
+        let png = custom_png(2, 2, 8, 2, &[0; 5]);
+        assert_eq!(png_error(&png), "invalid PNG: image data does not match the image size");
+    }
+
+    #[test]
+    fn chunks_carry_length_type_data_and_crc() {
+        let mut out = Vec::new();
+        write_chunk(&mut out, b"tEXt", b"abc").unwrap();
+
+        assert_eq!(&out[..4], &3u32.to_be_bytes());
+        assert_eq!(&out[4..11], b"tEXtabc");
+        assert_eq!(&out[11..], &crc32(b"tEXtabc").to_be_bytes());
+    }
+}
@@ -0,0 +1,389 @@
+use std::sync::Arc;
+
+use crate::{
+    Point3,
+    aabb::Aabb,
+    hittable::{Hit, HitRecord},
+    hittable_list::HittableList,
+    material::Material,
+    ray::Ray,
+    sampler::Sampler,
+    vec3::Vec3,
+};
+
+/// Parallelogram spanned by the edges `u` and `v` from the corner `q`.
+///
+/// The front face is the side `cross(u, v)` points to.
+pub struct Quad {
+    pub q: Point3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Arc<dyn Material>,
+    normal: Vec3,
+    d: f64,
+    w: Vec3,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
+        let n = Vec3::cross(&u, &v);
+        let normal = Vec3::unit_vector(n);
+        let d = Vec3::dot(&normal, &q);
+        // Used to project hit points onto the (u, v) basis.
+        let w = n / Vec3::dot(&n, &n);
+
+        Self { q, u, v, material, normal, d, w }
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        self.normal
+    }
+
+    pub fn area(&self) -> f64 {
+        Vec3::cross(&self.u, &self.v).length()
+    }
+}
+
+impl Quad {
+    /// Intersection with the quad; it never needs random numbers, so light
+    /// sampling can call it without a sampler.
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denom = Vec3::dot(&self.normal, ray.direction());
+
+        // The ray is parallel to the plane.
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - Vec3::dot(&self.normal, ray.origin())) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let p = ray.at(t);
+        let planar_hit = p - self.q;
+        let alpha = Vec3::dot(&self.w, &Vec3::cross(&planar_hit, &self.v));
+        let beta = Vec3::dot(&self.w, &Vec3::cross(&self.u, &planar_hit));
+
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut hit_record = HitRecord::new();
+
+        hit_record.t = t;
+        hit_record.p = p;
+        hit_record.u = alpha;
+        hit_record.v = beta;
+        hit_record.set_face_normal(ray, &self.normal);
+        hit_record.material = Arc::clone(&self.material);
+
+        Some(hit_record)
+    }
+}
+
+impl Hit for Quad {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, _sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        self.intersect(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let diagonal1 = Aabb::surrounding_box(
+            &Aabb::new(self.q, self.q),
+            &Aabb::new(self.q + self.u + self.v, self.q + self.u + self.v)
+        );
+        let diagonal2 = Aabb::surrounding_box(
+            &Aabb::new(self.q + self.u, self.q + self.u),
+            &Aabb::new(self.q + self.v, self.q + self.v)
+        );
+
+        Some(Aabb::surrounding_box(&diagonal1, &diagonal2).padded(1e-4))
+    }
+
+    /// Uniform over the quad's area, converted to solid angle.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        match self.intersect(&Ray::new(*origin, *direction), 0.001, f64::INFINITY) {
+            Some(hit_record) => area_pdf_to_solid_angle(&hit_record, direction, &self.normal, self.area()),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let (a, b) = sampler.random_2d();
+
+        Some(self.q + self.u * a + self.v * b - *origin)
+    }
+}
+
+/// Density per unit solid angle of hitting `hit_record` along `direction`,
+/// when points are picked uniformly on a flat surface of the given `area`.
+pub(crate) fn area_pdf_to_solid_angle(hit_record: &HitRecord, direction: &Vec3, normal: &Vec3, area: f64) -> f64 {
+    let distance_squared = hit_record.t * hit_record.t * direction.length_squared();
+    let cosine = (Vec3::dot(direction, normal) / direction.length()).abs();
+
+    distance_squared / (cosine * area)
+}
+
+/// Rectangle lying in a plane perpendicular to one of the coordinate axes.
+///
+/// It spans `[a0, a1]` x `[b0, b1]` on the other two axes (in x, y, z
+/// order) at `k` along its own axis, and faces towards the positive axis.
+pub struct AxisAlignedRect {
+    axis: usize,
+    a0: f64,
+    a1: f64,
+    b0: f64,
+    b1: f64,
+    k: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl AxisAlignedRect {
+    /// Rectangle in the plane z = k.
+    pub fn xy(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, material: Arc<dyn Material>) -> Self {
+        Self { axis: 2, a0: x0, a1: x1, b0: y0, b1: y1, k, material }
+    }
+
+    /// Rectangle in the plane y = k.
+    pub fn xz(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, material: Arc<dyn Material>) -> Self {
+        Self { axis: 1, a0: x0, a1: x1, b0: z0, b1: z1, k, material }
+    }
+
+    /// Rectangle in the plane x = k.
+    pub fn yz(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, material: Arc<dyn Material>) -> Self {
+        Self { axis: 0, a0: y0, a1: y1, b0: z0, b1: z1, k, material }
+    }
+
+    /// Indices of the two in-plane axes.
+    fn plane_axes(&self) -> (usize, usize) {
+        match self.axis {
+            0 => (1, 2),
+            1 => (0, 2),
+            _ => (0, 1),
+        }
+    }
+
+    fn point(&self, a: f64, b: f64, k: f64) -> Point3 {
+        match self.axis {
+            0 => Point3::new(k, a, b),
+            1 => Point3::new(a, k, b),
+            _ => Point3::new(a, b, k),
+        }
+    }
+}
+
+impl AxisAlignedRect {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (ia, ib) = self.plane_axes();
+        let t = (self.k - ray.origin()[self.axis]) / ray.direction()[self.axis];
+
+        // Also rejects NaN/infinite t from rays parallel to the plane.
+        if !(t >= t_min && t <= t_max) {
+            return None;
+        }
+
+        let a = ray.origin()[ia] + t * ray.direction()[ia];
+        let b = ray.origin()[ib] + t * ray.direction()[ib];
+        if a < self.a0 || a > self.a1 || b < self.b0 || b > self.b1 {
+            return None;
+        }
+
+        let mut hit_record = HitRecord::new();
+
+        hit_record.t = t;
+        hit_record.p = ray.at(t);
+        hit_record.u = (a - self.a0) / (self.a1 - self.a0);
+        hit_record.v = (b - self.b0) / (self.b1 - self.b0);
+        hit_record.set_face_normal(ray, &self.point(0.0, 0.0, 1.0));
+        hit_record.material = Arc::clone(&self.material);
+
+        Some(hit_record)
+    }
+}
+
+impl Hit for AxisAlignedRect {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, _sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        self.intersect(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            self.point(self.a0, self.b0, self.k),
+            self.point(self.a1, self.b1, self.k)
+        ).padded(1e-4))
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let area = (self.a1 - self.a0) * (self.b1 - self.b0);
+
+        match self.intersect(&Ray::new(*origin, *direction), 0.001, f64::INFINITY) {
+            Some(hit_record) => {
+                area_pdf_to_solid_angle(&hit_record, direction, &self.point(0.0, 0.0, 1.0), area)
+            }
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let (r1, r2) = sampler.random_2d();
+        let a = self.a0 + (self.a1 - self.a0) * r1;
+        let b = self.b0 + (self.b1 - self.b0) * r2;
+
+        Some(self.point(a, b, self.k) - *origin)
+    }
+}
+
+/// Axis-aligned box between two opposite corners, made of six outward-facing quads.
+pub struct Cuboid {
+    pub box_min: Point3,
+    pub box_max: Point3,
+    sides: HittableList,
+}
+
+impl Cuboid {
+    pub fn new(a: Point3, b: Point3, material: Arc<dyn Material>) -> Self {
+        let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+        let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+        let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+        let mut sides = HittableList::new();
+        // front, right, back, left, top, bottom
+        sides.add(Arc::new(Quad::new(Point3::new(min.x(), min.y(), max.z()), dx, dy, material.clone())));
+        sides.add(Arc::new(Quad::new(Point3::new(max.x(), min.y(), max.z()), -dz, dy, material.clone())));
+        sides.add(Arc::new(Quad::new(Point3::new(max.x(), min.y(), min.z()), -dx, dy, material.clone())));
+        sides.add(Arc::new(Quad::new(Point3::new(min.x(), min.y(), min.z()), dz, dy, material.clone())));
+        sides.add(Arc::new(Quad::new(Point3::new(min.x(), max.y(), max.z()), dx, -dz, material.clone())));
+        sides.add(Arc::new(Quad::new(Point3::new(min.x(), min.y(), min.z()), dx, dz, material)));
+
+        Self { box_min: min, box_max: max, sides }
+    }
+}
+
+impl Hit for Cuboid {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        self.sides.hit(ray, t_min, t_max, sampler)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.box_min, self.box_max).padded(1e-4))
+    }
+
+    /// Picks one of the six sides evenly, then a point on it.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        self.sides.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        self.sides.random(origin, sampler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color3, material::Lambertian, sampler::IndependentSampler};
+
+    const EPS: f64 = 1e-12;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color3::new(0.5, 0.5, 0.5)))
+    }
+
+    /// A slanted parallelogram in the z = 0 plane, facing +z.
+    fn slanted() -> Quad {
+        Quad::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            material(),
+        )
+    }
+
+    fn hit(object: &dyn Hit, origin: Point3, direction: Vec3) -> Option<HitRecord> {
+        object.hit(&Ray::new(origin, direction), 0.001, f64::INFINITY, &mut IndependentSampler::new(0))
+    }
+
+    fn down_at(x: f64, y: f64) -> Option<HitRecord> {
+        hit(&slanted(), Point3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn hits_only_inside_the_parallelogram() {
+        // Inside the quad's bounding rectangle, but outside its slanted sides.
+        for (x, y) in [(0.2, 0.5), (2.9, 0.2), (1.0, 1.1), (1.0, -0.1)] {
+            assert!(down_at(x, y).is_none(), "({}, {})", x, y);
+        }
+        for (x, y) in [(1.5, 0.5), (2.8, 0.9), (0.1, 0.05), (0.0, 0.0), (3.0, 1.0)] {
+            assert!(down_at(x, y).is_some(), "({}, {})", x, y);
+        }
+        assert!(hit(&slanted(), Point3::new(-1.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn maps_hits_to_edge_coordinates() {
+        let rec = down_at(1.5, 0.5).unwrap();
+        assert!((rec.t - 1.0).abs() < EPS);
+        assert!((rec.u - 0.5).abs() < EPS && (rec.v - 0.5).abs() < EPS, "({}, {})", rec.u, rec.v);
+
+        let rec = down_at(2.8, 0.9).unwrap();
+        assert!((rec.u - 0.95).abs() < EPS && (rec.v - 0.9).abs() < EPS, "({}, {})", rec.u, rec.v);
+    }
+
+    #[test]
+    fn faces_the_side_of_cross_u_v() {
+        assert_eq!(slanted().normal().z(), 1.0);
+
+        let front = down_at(1.5, 0.5).unwrap();
+        assert!(front.front_face);
+        assert_eq!(front.normal.z(), 1.0);
+
+        let back = hit(&slanted(), Point3::new(1.5, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!(!back.front_face);
+        assert_eq!(back.normal.z(), -1.0);
+    }
+
+    #[test]
+    fn axis_aligned_rects_map_uvs_along_their_axes() {
+        let rect = AxisAlignedRect::xz(1.0, 3.0, -2.0, 2.0, 5.0, material());
+        let rec = hit(&rect, Point3::new(1.5, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0)).unwrap();
+
+        assert!((rec.t - 5.0).abs() < EPS);
+        assert!((rec.u - 0.25).abs() < EPS && (rec.v - 0.75).abs() < EPS);
+        assert!(!rec.front_face);
+        assert!(hit(&rect, Point3::new(0.5, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn cuboid_sides_face_outwards() {
+        let (min, max) = (Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 2.0, 3.0));
+        let cuboid = Cuboid::new(max, min, material());
+        let center = (min + max) * 0.5;
+        assert_eq!([cuboid.box_min.x(), cuboid.box_max.z()], [0.0, 3.0]);
+
+        let mut sides = 0;
+        for axis in 0..3 {
+            for sign in [-1.0, 1.0] {
+                let mut outward = [0.0; 3];
+                outward[axis] = sign;
+                let outward = Vec3::new(outward[0], outward[1], outward[2]);
+
+                // From outside towards the center, the ray meets a front face.
+                let rec = hit(&cuboid, center + outward * 10.0, -outward).unwrap();
+                assert!(rec.front_face, "{:?}", outward);
+                assert!((rec.normal - outward).length() < EPS, "{:?}", rec.normal);
+                let face = if sign < 0.0 { min[axis] } else { max[axis] };
+                assert!((rec.p[axis] - face).abs() < EPS);
+
+                // From inside, the same side is a back face.
+                let rec = hit(&cuboid, center, outward).unwrap();
+                assert!(!rec.front_face, "{:?}", outward);
+                sides += 1;
+            }
+        }
+        assert_eq!(sides, 6);
+        assert_eq!(cuboid.sides.hittables_vec.len(), 6);
+    }
+}
@@ -3,13 +3,20 @@ use crate::{Point3, vec3::Vec3};
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
+    time: f64,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Self {
+        Self::with_time(origin, direction, 0.0)
+    }
+
+    /// Ray sent at `time`, which moving objects use to find their position.
+    pub fn with_time(origin: Point3, direction: Vec3, time: f64) -> Self {
         Self {
             origin,
             direction,
+            time,
         }
     }
 
@@ -21,6 +28,10 @@ impl Ray {
         &self.direction
     }
 
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
     pub fn at(&self, t: f64) -> Point3 {
         // self.origin + self.direction.multiply_coef(t)
         self.origin + self.direction * t
@@ -0,0 +1,806 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+        Mutex,
+    },
+    thread,
+};
+
+use crate::{
+    Color3,
+    camera::Camera,
+    filter::Filter,
+    hittable::{Hit, HitRecord},
+    color,
+    hittable_list::HittableList,
+    image::{Image, Welford},
+    material::ScatterRecord,
+    pdf::{HittablePdf, Pdf},
+    ray::Ray,
+    sampler::{Sampler, SamplerKind},
+};
+
+/// What a ray that leaves the scene sees.
+#[derive(Debug, Clone, Copy)]
+pub enum Background {
+    /// The white-to-blue sky gradient, which also lights the scene.
+    Sky,
+    Solid(Color3),
+}
+
+impl Background {
+    pub fn color(&self, ray: &Ray) -> Color3 {
+        match self {
+            Background::Sky => {
+                let unit_direction = Color3::unit_vector(*ray.direction());
+                let t = 0.5 * (unit_direction.y() + 1.0);
+
+                Color3::new(1.0, 1.0, 1.0) * (1.0 - t) + Color3::new(0.5, 0.7, 1.0) * t
+            }
+            Background::Solid(color) => *color,
+        }
+    }
+}
+
+/// Power heuristic weight of a sample taken with density `pdf` when the
+/// other strategy would have produced it with density `other_pdf`.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+
+    if a + b > 0.0 { a / (a + b) } else { 0.0 }
+}
+
+/// Next-event estimate: light reaching `hit_rec` from a point sampled on `lights`.
+fn sample_light(
+        ray_in: &Ray,
+        hit_rec: &HitRecord,
+        attenuation: Color3,
+        bsdf_pdf: &dyn Pdf,
+        world: &dyn Hit,
+        lights: &HittableList,
+        sampler: &mut dyn Sampler
+    ) -> Color3 {
+    let black = Color3::new(0.0, 0.0, 0.0);
+    let light_pdf = HittablePdf::new(lights, hit_rec.p);
+    let direction = match light_pdf.generate(sampler) {
+        Some(direction) => direction,
+        None => return black,
+    };
+    let shadow_ray = Ray::with_time(hit_rec.p, direction, ray_in.time());
+
+    let light_pdf_value = light_pdf.value(shadow_ray.direction());
+    let scattering_pdf = hit_rec.material.scattering_pdf(ray_in, hit_rec, &shadow_ray);
+    if light_pdf_value <= 0.0 || scattering_pdf <= 0.0 {
+        return black;
+    }
+
+    // Whatever the shadow ray hits first is what the light sample sees.
+    let light_hit = match world.hit(&shadow_ray, 0.001, f64::INFINITY, sampler) {
+        Some(light_hit) => light_hit,
+        None => return black,
+    };
+    let radiance = light_hit.material.emitted(&shadow_ray, &light_hit);
+    let weight = power_heuristic(light_pdf_value, bsdf_pdf.value(shadow_ray.direction()));
+
+    attenuation * scattering_pdf * radiance * weight / light_pdf_value
+}
+
+/// How the camera paths of a render ended and how long they were, for
+/// comparing termination strategies.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathStats {
+    pub paths: u64,
+    /// Total number of surfaces hit over all paths.
+    pub bounces: u64,
+    pub longest: u32,
+    /// Paths that left the scene.
+    pub escaped: u64,
+    /// Paths that ended on a surface that scatters nothing, such as a light.
+    pub absorbed: u64,
+    /// Paths ended by Russian roulette.
+    pub roulette: u64,
+    /// Paths cut off at the maximum depth.
+    pub depth_limit: u64,
+}
+
+impl PathStats {
+    pub fn mean_depth(&self) -> f64 {
+        if self.paths == 0 { 0.0 } else { self.bounces as f64 / self.paths as f64 }
+    }
+
+    pub fn merge(&mut self, other: &PathStats) {
+        self.paths += other.paths;
+        self.bounces += other.bounces;
+        self.longest = self.longest.max(other.longest);
+        self.escaped += other.escaped;
+        self.absorbed += other.absorbed;
+        self.roulette += other.roulette;
+        self.depth_limit += other.depth_limit;
+    }
+
+    fn record(&mut self, depth: u32) {
+        self.paths += 1;
+        self.bounces += depth as u64;
+        self.longest = self.longest.max(depth);
+    }
+}
+
+impl fmt::Display for PathStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |n: u64| if self.paths == 0 { 0.0 } else { 100.0 * n as f64 / self.paths as f64 };
+
+        write!(
+            f,
+            "{} paths, mean depth {:.2}, longest {}; escaped {:.1}%, absorbed {:.1}%, roulette {:.1}%, depth limit {:.1}%",
+            self.paths,
+            self.mean_depth(),
+            self.longest,
+            percent(self.escaped),
+            percent(self.absorbed),
+            percent(self.roulette),
+            percent(self.depth_limit),
+        )
+    }
+}
+
+/// One progressive pass: `samples` more samples for every pixel, drawn with
+/// the pass index mixed into the seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pass {
+    pub index: u32,
+    pub samples: u32,
+}
+
+/// Adaptive sampling: a pixel stops taking samples once it has at least
+/// `min_samples` and the 95% confidence interval of its mean luminance is
+/// narrower than `threshold` times the mean.
+///
+/// The test only runs every `min_samples` samples. Stopping as soon as a
+/// noisy estimate looks good would favor lucky streaks and bias the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: u32,
+    pub threshold: f64,
+}
+
+impl AdaptiveSampling {
+    /// Means below this are measured against it instead, so near-black pixels
+    /// do not need ever tighter intervals.
+    const MIN_REFERENCE: f64 = 0.01;
+
+    pub fn converged(&self, stats: &Welford) -> bool {
+        let batch = self.min_samples.max(2);
+        if stats.count < batch || !stats.count.is_multiple_of(batch) {
+            return false;
+        }
+        let half_width = 1.96 * (stats.variance() / stats.count as f64).sqrt();
+
+        half_width <= self.threshold * stats.mean.max(Self::MIN_REFERENCE)
+    }
+}
+
+/// What one pass did in a scanline: the samples taken in each of its pixels
+/// with their updated statistics, and the filtered contributions of those
+/// samples to the rows around it.
+struct Scanline {
+    pixels: Vec<(u32, Welford)>,
+    /// First output row the splats reach.
+    first_row: u32,
+    /// Weighted color and weight sums, a full row of pixels per output row
+    /// from `first_row` on.
+    splats: Vec<(Color3, f64)>,
+}
+
+/// Scanlines waiting to be added to the image, which happens strictly in
+/// row order so the sums come out the same however the rows are scheduled.
+struct Merge<'a> {
+    image: &'a mut Image,
+    next_row: u32,
+    pending: BTreeMap<u32, Scanline>,
+}
+
+impl Merge<'_> {
+    fn add(&mut self, row: u32, scanline: Scanline) {
+        self.pending.insert(row, scanline);
+
+        while let Some(scanline) = self.pending.remove(&self.next_row) {
+            let width = self.image.width();
+            for (x, &(samples, stats)) in scanline.pixels.iter().enumerate() {
+                self.image.count_samples(x as u32, self.next_row, samples);
+                self.image.set_pixel_stats(x as u32, self.next_row, stats);
+            }
+            for (k, &(weighted_sum, weight)) in scanline.splats.iter().enumerate() {
+                if weight != 0.0 {
+                    let y = scanline.first_row + k as u32 / width;
+                    self.image.splat(k as u32 % width, y, weighted_sum, weight);
+                }
+            }
+            self.next_row += 1;
+        }
+    }
+}
+
+/// Bounces after which Russian roulette may end a path unless told otherwise.
+pub const DEFAULT_ROULETTE_DEPTH: u32 = 3;
+
+pub struct Renderer {
+    pub image_width: u32,
+    pub image_height: u32,
+    pub samples_per_pixel: u32,
+    /// Hard limit on bounces. With Russian roulette on, paths rarely get
+    /// this far; it only stops ones that would never lose energy.
+    pub max_depth: i32,
+    /// Bounces after which Russian roulette may end a path, or `None` to cut
+    /// every path at `max_depth` instead.
+    pub roulette_depth: Option<u32>,
+    /// Lets pixels stop early; `samples_per_pixel` or the pass size is then
+    /// the most a pixel gets.
+    pub adaptive: Option<AdaptiveSampling>,
+    pub sampler: SamplerKind,
+    /// How samples are weighted into the pixels around them.
+    pub filter: Filter,
+    pub threads: usize,
+    /// Global seed every pixel's sampler is derived from.
+    pub seed: u64,
+    pub background: Background,
+    /// Called from the worker threads with the number of scanlines left in
+    /// the pass each time one finishes.
+    pub progress: Option<Arc<dyn Fn(u32) + Send + Sync>>,
+}
+
+impl Renderer {
+    pub fn new(
+            image_width: u32,
+            image_height: u32,
+            samples_per_pixel: u32,
+            max_depth: i32
+        ) -> Self {
+        let threads = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+
+        Self {
+            image_width,
+            image_height,
+            samples_per_pixel,
+            max_depth,
+            roulette_depth: Some(DEFAULT_ROULETTE_DEPTH),
+            adaptive: None,
+            sampler: SamplerKind::Independent,
+            filter: Filter::default(),
+            threads,
+            seed: 0,
+            background: Background::Sky,
+            progress: None,
+        }
+    }
+
+    /// Renders the image on `self.threads` worker threads, one scanline at a time.
+    ///
+    /// The returned image keeps the summed samples of each pixel so callers can
+    /// post-process the linear values before encoding.
+    pub fn render(&self, world: &dyn Hit, lights: &HittableList, camera: &Camera) -> Image {
+        self.render_with_stats(world, lights, camera).0
+    }
+
+    /// Like [`Renderer::render`], also reporting how the paths ended.
+    pub fn render_with_stats(&self, world: &dyn Hit, lights: &HittableList, camera: &Camera) -> (Image, PathStats) {
+        let mut image = Image::new(self.image_width, self.image_height);
+        let pass = Pass { index: 0, samples: self.samples_per_pixel };
+        let stats = self.render_pass(world, lights, camera, pass, &mut image);
+
+        (image, stats)
+    }
+
+    /// Adds one pass of samples to every pixel of `image`, which must match
+    /// the renderer's size. With adaptive sampling, pixels that have
+    /// converged get fewer samples or none.
+    ///
+    /// Running passes 0, 1, 2, ... into the same image converges to the same
+    /// result as a single render with their total sample count.
+    pub fn render_pass(
+            &self,
+            world: &dyn Hit,
+            lights: &HittableList,
+            camera: &Camera,
+            pass: Pass,
+            image: &mut Image
+        ) -> PathStats {
+        assert!(
+            image.width() == self.image_width && image.height() == self.image_height,
+            "image size does not match the renderer"
+        );
+
+        // Sample counts and statistics from earlier passes, which the
+        // scanlines read while finished rows are merged into the image.
+        let previous: Vec<(u32, Welford)> = (0..self.image_height)
+            .flat_map(|y| (0..self.image_width).map(move |x| (x, y)))
+            .map(|(x, y)| (image.sample_count(x, y), image.pixel_stats(x, y)))
+            .collect();
+        let next_row = AtomicU32::new(0);
+        let rows_done = AtomicU32::new(0);
+        let merge = Mutex::new(Merge { image, next_row: 0, pending: BTreeMap::new() });
+        let stats = Mutex::new(PathStats::default());
+
+        thread::scope(|s| {
+            for _ in 0..self.threads.max(1) {
+                s.spawn(|| loop {
+                    let row = next_row.fetch_add(1, Ordering::Relaxed);
+                    if row >= self.image_height {
+                        break;
+                    }
+
+                    // Row 0 of the output is the top scanline, j = IMAGE_HEIGHT - 1.
+                    let j = self.image_height - 1 - row;
+                    let (scanline, row_stats) = self.render_scanline(world, lights, camera, pass, &previous, j);
+
+                    merge.lock().unwrap().add(row, scanline);
+                    stats.lock().unwrap().merge(&row_stats);
+
+                    let done = rows_done.fetch_add(1, Ordering::Relaxed) + 1;
+                    if let Some(progress) = &self.progress {
+                        progress(self.image_height - done);
+                    }
+                });
+            }
+        });
+
+        stats.into_inner().unwrap()
+    }
+
+    fn render_scanline(
+            &self,
+            world: &dyn Hit,
+            lights: &HittableList,
+            camera: &Camera,
+            pass: Pass,
+            previous: &[(u32, Welford)],
+            j: u32
+        ) -> (Scanline, PathStats) {
+        let width_minus_one = (self.image_width - 1).max(1) as f64;
+        let height_minus_one = (self.image_height - 1).max(1) as f64;
+        let row = self.image_height - 1 - j;
+        let mut stats = PathStats::default();
+
+        // Output rows within reach of the filter.
+        let reach = (self.filter.radius + 0.5).ceil() as u32;
+        let first_row = row.saturating_sub(reach);
+        let last_row = (row + reach).min(self.image_height - 1);
+        let width = self.image_width as usize;
+        let mut splats = vec![(Color3::new(0.0, 0.0, 0.0), 0.0); (last_row - first_row + 1) as usize * width];
+
+        // Pixels whose centers lie within the filter radius of film position `p`.
+        let max_x = self.image_width as i64 - 1;
+        let max_j = self.image_height as i64 - 1;
+        let radius = self.filter.radius;
+        let covered = |p: f64, max: i64| {
+            ((p - 0.5 - radius).ceil() as i64).max(0)..=((p - 0.5 + radius).floor() as i64).min(max)
+        };
+
+        let pixels = (0..self.image_width).map(|i| {
+            let (mut samples, mut pixel_stats) = previous[(row * self.image_width + i) as usize];
+            let mut sampler = self.sampler.for_pixel(self.seed, pass.index, i, j, self.samples_per_pixel);
+            // Sample indices carry on from earlier passes.
+            let first_sample = samples;
+
+            for _ in 0..pass.samples {
+                if self.adaptive.is_some_and(|adaptive| adaptive.converged(&pixel_stats)) {
+                    break;
+                }
+                sampler.start_sample(samples);
+
+                let (du, dv) = sampler.random_2d();
+                let (film_x, film_y) = (i as f64 + du, j as f64 + dv);
+                let ray = camera.get_ray(film_x / width_minus_one, film_y / height_minus_one, sampler.as_mut());
+                let color = self.ray_color(&ray, world, lights, sampler.as_mut(), &mut stats);
+
+                samples += 1;
+                pixel_stats.add(color::luminance(color));
+
+                for py in covered(film_y, max_j) {
+                    for px in covered(film_x, max_x) {
+                        let weight = self.filter.evaluate(film_x - (px as f64 + 0.5), film_y - (py as f64 + 0.5));
+                        if weight != 0.0 {
+                            let target_row = self.image_height - 1 - py as u32;
+                            let splat = &mut splats[(target_row - first_row) as usize * width + px as usize];
+                            splat.0 += color * weight;
+                            splat.1 += weight;
+                        }
+                    }
+                }
+            }
+
+            (samples - first_sample, pixel_stats)
+        }).collect();
+
+        (Scanline { pixels, first_row, splats }, stats)
+    }
+
+    /// Radiance arriving along `ray`, traced as a path that carries its
+    /// throughput (the product of `bsdf * cos / pdf` so far) from bounce to
+    /// bounce.
+    ///
+    /// Diffuse bounces combine two estimates with multiple importance sampling:
+    /// a shadow ray towards a point picked on `lights` (next-event estimation)
+    /// and the continuation of the path in a direction drawn from the material.
+    /// With no lights, only the latter remains.
+    ///
+    /// After `roulette_depth` bounces, each path survives with a probability
+    /// given by its throughput and is boosted by the inverse of it, so dim paths
+    /// end early without biasing the estimate.
+    pub fn ray_color(
+            &self,
+            ray: &Ray,
+            world: &dyn Hit,
+            lights: &HittableList,
+            sampler: &mut dyn Sampler,
+            stats: &mut PathStats
+        ) -> Color3 {
+        let mut color = Color3::new(0.0, 0.0, 0.0);
+        let mut throughput = Color3::new(1.0, 1.0, 1.0);
+        let mut ray = Ray::with_time(*ray.origin(), *ray.direction(), ray.time());
+        // Density the material sampled `ray` with, or `None` for camera rays
+        // and specular bounces, whose light nothing else could find.
+        let mut bsdf_pdf: Option<f64> = None;
+        let mut depth = 0;
+
+        loop {
+            if depth >= self.max_depth.max(0) as u32 {
+                stats.depth_limit += 1;
+                break;
+            }
+
+            let hit_rec = match world.hit(&ray, 0.001, f64::INFINITY, sampler) {
+                Some(hit_rec) => hit_rec,
+                None => {
+                    color += throughput * self.background.color(&ray);
+                    stats.escaped += 1;
+                    break;
+                }
+            };
+            depth += 1;
+
+            // Light sampling at the previous bounce may have found this emitter too.
+            let mut emitted = hit_rec.material.emitted(&ray, &hit_rec);
+            if let Some(pdf) = bsdf_pdf {
+                emitted = emitted * power_heuristic(pdf, lights.pdf_value(ray.origin(), ray.direction()));
+            }
+            color += throughput * emitted;
+
+            match hit_rec.material.scatter(&ray, &hit_rec, sampler) {
+                None => {
+                    stats.absorbed += 1;
+                    break;
+                }
+                Some(ScatterRecord::Specular { attenuation, ray: scattered }) => {
+                    throughput = throughput * attenuation;
+                    ray = scattered;
+                    bsdf_pdf = None;
+                }
+                Some(ScatterRecord::Diffuse { attenuation, pdf }) => {
+                    if !lights.hittables_vec.is_empty() {
+                        color += throughput * sample_light(&ray, &hit_rec, attenuation, pdf.as_ref(), world, lights, sampler);
+                    }
+
+                    let scattered = match pdf.generate(sampler) {
+                        Some(direction) => Ray::with_time(hit_rec.p, direction, ray.time()),
+                        None => {
+                            stats.absorbed += 1;
+                            break;
+                        }
+                    };
+                    let pdf_value = pdf.value(scattered.direction());
+                    if pdf_value <= 0.0 {
+                        stats.absorbed += 1;
+                        break;
+                    }
+
+                    let bsdf = attenuation * hit_rec.material.scattering_pdf(&ray, &hit_rec, &scattered);
+                    throughput = throughput * bsdf / pdf_value;
+                    ray = scattered;
+                    bsdf_pdf = Some(pdf_value);
+                }
+            }
+
+            if matches!(self.roulette_depth, Some(min) if depth >= min) {
+                let survival = throughput.x().max(throughput.y()).max(throughput.z()).min(1.0);
+                if survival <= 0.0 || sampler.random() >= survival {
+                    stats.roulette += 1;
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+        }
+
+        stats.record(depth);
+
+        color
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Point3,
+        bvh::BvhNode,
+        camera::Camera,
+        filter::FilterKind,
+        material::{DiffuseLight, Lambertian, Material},
+        quad::AxisAlignedRect,
+        sampler::IndependentSampler,
+        scenes,
+        vec3::Vec3,
+    };
+
+    /// Renders two passes of the Cornell box on `threads` threads.
+    fn render_cornell(threads: usize, configure: impl Fn(&mut Renderer)) -> Image {
+        let scene = scenes::by_name("cornell-box", 0).unwrap();
+        let world = BvhNode::new(&scene.world).unwrap();
+        let camera = scene.camera.build(1.0);
+
+        let mut renderer = Renderer::new(12, 12, 8, 8);
+        renderer.background = scene.background;
+        renderer.threads = threads;
+        configure(&mut renderer);
+
+        let mut image = Image::new(12, 12);
+        for (index, samples) in [(0, 3), (1, 5)] {
+            renderer.render_pass(&world, &scene.lights, &camera, Pass { index, samples }, &mut image);
+        }
+
+        image
+    }
+
+    fn assert_identical(a: &Image, b: &Image) {
+        let bits = |c: Color3| [c.x(), c.y(), c.z()].map(f64::to_bits);
+
+        for y in 0..a.height() {
+            for x in 0..a.width() {
+                assert_eq!(bits(a.pixel_sum(x, y)), bits(b.pixel_sum(x, y)), "pixel ({}, {})", x, y);
+                assert_eq!(a.weight(x, y).to_bits(), b.weight(x, y).to_bits(), "pixel ({}, {})", x, y);
+                assert_eq!(a.sample_count(x, y), b.sample_count(x, y), "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn output_does_not_depend_on_the_thread_count() {
+        let default = |_: &mut Renderer| {};
+
+        assert_identical(&render_cornell(1, default), &render_cornell(4, default));
+    }
+
+    #[test]
+    fn splatting_and_adaptive_output_do_not_depend_on_the_thread_count() {
+        let configure = |renderer: &mut Renderer| {
+            renderer.sampler = SamplerKind::Sobol;
+            renderer.filter = Filter::new(FilterKind::Mitchell, 2.0);
+            renderer.adaptive = Some(AdaptiveSampling { min_samples: 2, threshold: 0.5 });
+        };
+
+        assert_identical(&render_cornell(1, configure), &render_cornell(4, configure));
+    }
+
+    fn stats(values: impl IntoIterator<Item = f64>) -> Welford {
+        let mut stats = Welford::default();
+        values.into_iter().for_each(|v| stats.add(v));
+        stats
+    }
+
+    #[test]
+    fn convergence_is_only_tested_after_whole_batches() {
+        let adaptive = AdaptiveSampling { min_samples: 8, threshold: 0.05 };
+
+        for count in [1, 7, 9, 15] {
+            assert!(!adaptive.converged(&stats((0..count).map(|_| 0.5))), "{}", count);
+        }
+        assert!(adaptive.converged(&stats((0..8).map(|_| 0.5))));
+        assert!(adaptive.converged(&stats((0..16).map(|_| 0.5))));
+
+        // Below two samples there is no variance to judge.
+        let eager = AdaptiveSampling { min_samples: 0, threshold: 0.05 };
+        assert!(!eager.converged(&stats([0.5])));
+        assert!(eager.converged(&stats([0.5, 0.5])));
+    }
+
+    #[test]
+    fn convergence_compares_the_confidence_interval_with_the_mean() {
+        let adaptive = AdaptiveSampling { min_samples: 16, threshold: 0.05 };
+        // 16 values alternating around the mean by d: the variance is
+        // 16 d^2 / 15, so the half-width is 1.96 d / sqrt(15).
+        let alternating = |mean: f64, d: f64| stats((0..16).map(move |i| if i % 2 == 0 { mean + d } else { mean - d }));
+        let d = 0.05 * 15.0f64.sqrt() / 1.96;
+
+        assert!(adaptive.converged(&alternating(1.0, d * 0.99)));
+        assert!(!adaptive.converged(&alternating(1.0, d * 1.01)));
+
+        // Near-black pixels are measured against the 0.01 floor, not their mean.
+        assert!(adaptive.converged(&alternating(0.001, 0.01 * d * 0.99)));
+        assert!(!adaptive.converged(&alternating(0.001, 0.01 * d * 1.01)));
+    }
+
+    /// The unit cube seen from inside, with walls of the given albedo and
+    /// optionally a white light of radiance `emit` as its top face.
+    fn furnace(albedo: f64, emit: Option<f64>) -> HittableList {
+        let wall: Arc<dyn Material> = Arc::new(Lambertian::new(Color3::new(albedo, albedo, albedo)));
+        let top: Arc<dyn Material> = match emit {
+            Some(emit) => Arc::new(DiffuseLight::new(Color3::new(emit, emit, emit))),
+            None => wall.clone(),
+        };
+
+        let mut world = HittableList::new();
+        world.add(Arc::new(AxisAlignedRect::xz(0.0, 1.0, 0.0, 1.0, 1.0, top)));
+        world.add(Arc::new(AxisAlignedRect::xz(0.0, 1.0, 0.0, 1.0, 0.0, wall.clone())));
+        for k in [0.0, 1.0] {
+            world.add(Arc::new(AxisAlignedRect::xy(0.0, 1.0, 0.0, 1.0, k, wall.clone())));
+            world.add(Arc::new(AxisAlignedRect::yz(0.0, 1.0, 0.0, 1.0, k, wall.clone())));
+        }
+
+        world
+    }
+
+    /// Traces `count` paths from the middle of `world` in random directions,
+    /// returning the mean and variance of their radiance.
+    fn trace_from_center(renderer: &Renderer, world: &HittableList, count: usize, stats: &mut PathStats) -> (f64, f64) {
+        let mut sampler = IndependentSampler::new(renderer.seed);
+        let mut radiance = Welford::default();
+
+        for _ in 0..count {
+            let ray = Ray::new(Point3::new(0.5, 0.5, 0.5), Vec3::random_unit_vector(&mut sampler));
+            radiance.add(renderer.ray_color(&ray, world, &HittableList::new(), &mut sampler, stats).x());
+        }
+
+        (radiance.mean, radiance.variance())
+    }
+
+    #[test]
+    fn russian_roulette_does_not_bias_the_furnace() {
+        let world = furnace(0.8, Some(1.0));
+        let count = 20_000;
+
+        let mut full = Renderer::new(1, 1, 1, 200);
+        full.roulette_depth = None;
+        let mut full_stats = PathStats::default();
+        let (full_mean, full_variance) = trace_from_center(&full, &world, count, &mut full_stats);
+
+        let mut roulette = Renderer::new(1, 1, 1, 200);
+        roulette.roulette_depth = Some(1);
+        roulette.seed = 1;
+        let mut roulette_stats = PathStats::default();
+        let (roulette_mean, roulette_variance) = trace_from_center(&roulette, &world, count, &mut roulette_stats);
+
+        // Roulette really ended paths early...
+        assert!(roulette_stats.roulette > count as u64 / 4, "{}", roulette_stats);
+        assert!(roulette_stats.mean_depth() < full_stats.mean_depth());
+        // ...without moving the mean by more than four standard errors.
+        let error = ((full_variance + roulette_variance) / count as f64).sqrt();
+        assert!(
+            (full_mean - roulette_mean).abs() < 4.0 * error,
+            "{} vs {} (standard error {})", full_mean, roulette_mean, error
+        );
+    }
+
+    #[test]
+    fn path_stats_count_how_paths_end() {
+        let mut sampler = IndependentSampler::new(0);
+        let from_center = |direction: Vec3| Ray::new(Point3::new(0.5, 0.5, 0.5), direction);
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let lights = HittableList::new();
+        let mut renderer = Renderer::new(1, 1, 1, 4);
+        renderer.roulette_depth = None;
+
+        let mut stats = PathStats::default();
+        renderer.ray_color(&from_center(up), &HittableList::new(), &lights, &mut sampler, &mut stats);
+        renderer.ray_color(&from_center(-up), &HittableList::new(), &lights, &mut sampler, &mut stats);
+        assert_eq!(stats, PathStats { paths: 2, escaped: 2, ..Default::default() });
+
+        let mut stats = PathStats::default();
+        renderer.ray_color(&from_center(up), &furnace(0.8, Some(1.0)), &lights, &mut sampler, &mut stats);
+        assert_eq!(stats, PathStats { paths: 1, bounces: 1, longest: 1, absorbed: 1, ..Default::default() });
+
+        // With no light to reach, every path runs into the depth limit.
+        let mut stats = PathStats::default();
+        for _ in 0..3 {
+            renderer.ray_color(&from_center(-up), &furnace(0.8, None), &lights, &mut sampler, &mut stats);
+        }
+        assert_eq!(stats, PathStats { paths: 3, bounces: 12, longest: 4, depth_limit: 3, ..Default::default() });
+
+        // Black walls leave nothing to survive on once roulette starts.
+        renderer.roulette_depth = Some(2);
+        let mut stats = PathStats::default();
+        renderer.ray_color(&from_center(-up), &furnace(0.0, None), &lights, &mut sampler, &mut stats);
+        assert_eq!(stats, PathStats { paths: 1, bounces: 2, longest: 2, roulette: 1, ..Default::default() });
+
+        renderer.max_depth = 0;
+        let mut stats = PathStats::default();
+        renderer.ray_color(&from_center(-up), &furnace(0.8, Some(1.0)), &lights, &mut sampler, &mut stats);
+        assert_eq!(stats, PathStats { paths: 1, depth_limit: 1, ..Default::default() });
+    }
+
+    fn assert_color(actual: Color3, expected: [f64; 3]) {
+        let expected = Color3::new(expected[0], expected[1], expected[2]);
+        assert!((actual - expected).length() < 1e-12, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn misses_see_the_background() {
+        let mut sampler = IndependentSampler::new(0);
+        let mut stats = PathStats::default();
+        let empty = HittableList::new();
+        let ray = |y| Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, y, 0.0));
+        let mut renderer = Renderer::new(1, 1, 1, 4);
+
+        renderer.background = Background::Solid(Color3::new(0.1, 0.2, 0.3));
+        assert_color(renderer.ray_color(&ray(1.0), &empty, &empty, &mut sampler, &mut stats), [0.1, 0.2, 0.3]);
+
+        renderer.background = Background::Sky;
+        assert_color(renderer.ray_color(&ray(1.0), &empty, &empty, &mut sampler, &mut stats), [0.5, 0.7, 1.0]);
+        assert_color(renderer.ray_color(&ray(-1.0), &empty, &empty, &mut sampler, &mut stats), [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn emitters_add_their_radiance() {
+        let mut sampler = IndependentSampler::new(0);
+        let mut stats = PathStats::default();
+        let empty = HittableList::new();
+        let mut world = HittableList::new();
+        let light = Arc::new(DiffuseLight::new(Color3::new(2.0, 3.0, 4.0)));
+        world.add(Arc::new(AxisAlignedRect::xy(-1.0, 1.0, -1.0, 1.0, -1.0, light)));
+
+        let mut renderer = Renderer::new(1, 1, 1, 4);
+        renderer.background = Background::Solid(Color3::new(0.5, 0.5, 0.5));
+        let ray = |z| Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, z));
+
+        // The light replaces the background it hides; it does not add to it.
+        assert_color(renderer.ray_color(&ray(-1.0), &world, &empty, &mut sampler, &mut stats), [2.0, 3.0, 4.0]);
+        assert_color(renderer.ray_color(&ray(1.0), &world, &empty, &mut sampler, &mut stats), [0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn renders_emitters_over_the_background() {
+        let mut world = HittableList::new();
+        let light = Arc::new(DiffuseLight::new(Color3::new(2.0, 3.0, 4.0)));
+        // Covers the right half of the view.
+        world.add(Arc::new(AxisAlignedRect::xy(0.0, 10.0, -10.0, 10.0, -1.0, light)));
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let camera = Camera::new(origin, Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 90.0, 1.0, 0.0, 1.0);
+
+        let mut renderer = Renderer::new(4, 4, 4, 4);
+        renderer.threads = 1;
+        renderer.background = Background::Solid(Color3::new(0.1, 0.2, 0.3));
+        let image = renderer.render(&world, &HittableList::new(), &camera);
+
+        for y in 0..4 {
+            assert_color(image.average(0, y), [0.1, 0.2, 0.3]);
+            assert_color(image.average(3, y), [2.0, 3.0, 4.0]);
+        }
+    }
+
+    #[test]
+    fn reports_each_finished_scanline() {
+        let camera = Camera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let remaining = Arc::new(Mutex::new(Vec::new()));
+        let mut renderer = Renderer::new(3, 5, 1, 2);
+        renderer.threads = 2;
+        let report = remaining.clone();
+        renderer.progress = Some(Arc::new(move |left| report.lock().unwrap().push(left)));
+
+        renderer.render(&HittableList::new(), &HittableList::new(), &camera);
+
+        let mut remaining = remaining.lock().unwrap().clone();
+        remaining.sort();
+        assert_eq!(remaining, [0, 1, 2, 3, 4]);
+    }
+}
@@ -1,13 +1,3 @@
-use rand::Rng;
-
-pub fn random_in_range(min: f64, max: f64) -> f64 {
-    rand::thread_rng().gen_range(min..max)
-}
-
-pub fn random() -> f64 {
-    random_in_range(0.0, 1.0)
-}
-
 pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
     if x < min {
         min
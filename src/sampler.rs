@@ -0,0 +1,518 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+/// Source of the numbers everything sampled while rendering is drawn from.
+///
+/// A pixel's samples are numbered, and each one reads its dimensions in a
+/// fixed order: pixel offset, lens position, time, then whatever the path
+/// needs at each bounce. Samplers that know the sample index and dimension can
+/// spread the values more evenly than independent random numbers.
+pub trait Sampler {
+    /// Starts sample `index` of the pixel; the next value drawn is its first
+    /// dimension.
+    fn start_sample(&mut self, index: u32);
+
+    /// Next dimension of the current sample, uniform in `[0, 1)`.
+    fn random(&mut self) -> f64;
+
+    /// Next two dimensions, stratified together where the sampler can.
+    fn random_2d(&mut self) -> (f64, f64) {
+        let x = self.random();
+
+        (x, self.random())
+    }
+
+    fn random_in_range(&mut self, min: f64, max: f64) -> f64 {
+        min + (max - min) * self.random()
+    }
+}
+
+/// The samplers a render can use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SamplerKind {
+    #[default]
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "independent" | "random" => Some(SamplerKind::Independent),
+            "stratified" => Some(SamplerKind::Stratified),
+            "halton" => Some(SamplerKind::Halton),
+            "sobol" => Some(SamplerKind::Sobol),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SamplerKind::Independent => "independent",
+            SamplerKind::Stratified => "stratified",
+            SamplerKind::Halton => "halton",
+            SamplerKind::Sobol => "sobol",
+        }
+    }
+
+    /// Sampler for pixel (x, y) in progressive pass `pass`, where y counts
+    /// scanlines from the bottom.
+    ///
+    /// `samples_per_pixel` is how many samples the stratified sampler spreads
+    /// its strata over. The other deterministic samplers only depend on the
+    /// sample index, which carries on from pass to pass.
+    pub fn for_pixel(self, seed: u64, pass: u32, x: u32, y: u32, samples_per_pixel: u32) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::for_pass(seed, pass, x, y)),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(pixel_seed(seed, x, y), samples_per_pixel)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new(pixel_seed(seed, x, y))),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(pixel_seed(seed, x, y))),
+        }
+    }
+}
+
+/// SplitMix64 finalizer, used to decorrelate nearby seeds.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0u64, |acc, &v| mix(acc ^ v))
+}
+
+/// Uniform number in `[0, 1)` from a hash.
+fn to_unit(hash: u64) -> f64 {
+    // The top 53 bits fill an f64 mantissa exactly.
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn pixel_seed(seed: u64, x: u32, y: u32) -> u64 {
+    mix(mix(seed) ^ (((y as u64) << 32) | x as u64))
+}
+
+/// Independent uniform random numbers, whatever the sample index.
+///
+/// Each pixel gets its own sampler derived from the global seed and the pixel
+/// coordinates, so a render is reproducible no matter how the pixels are
+/// spread over threads.
+pub struct IndependentSampler {
+    rng: StdRng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: StdRng::seed_from_u64(seed) }
+    }
+
+    /// Sampler for pixel (x, y), where y counts scanlines from the bottom.
+    pub fn for_pixel(seed: u64, x: u32, y: u32) -> Self {
+        Self::for_pass(seed, 0, x, y)
+    }
+
+    /// Sampler for pixel (x, y) in progressive pass `pass`, so every pass
+    /// draws fresh samples. Pass 0 is the same as [`IndependentSampler::for_pixel`].
+    pub fn for_pass(seed: u64, pass: u32, x: u32, y: u32) -> Self {
+        let pixel = ((y as u64) << 32) | x as u64;
+
+        // mix(0) == 0, which keeps pass 0 unchanged.
+        Self::new(mix(mix(seed) ^ pixel ^ mix(pass as u64)))
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_sample(&mut self, _index: u32) {}
+
+    fn random(&mut self) -> f64 {
+        self.rng.gen::<f64>()
+    }
+}
+
+/// Element `i` of a pseudo-random permutation of `0..len` chosen by `seed`
+/// (Kensler, "Correlated Multi-Jittered Sampling").
+fn permute(mut i: u32, len: u32, seed: u32) -> u32 {
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    // Permute within the next power of two until the result lands in 0..len.
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+
+        if i < len {
+            break;
+        }
+    }
+
+    i.wrapping_add(seed) % len
+}
+
+/// Jittered sampling: each dimension (or pair of dimensions) is cut into as
+/// many strata as there are samples per pixel, and every sample lands in a
+/// different one, at a random spot inside it.
+///
+/// The strata are shuffled independently for each dimension so that the
+/// dimensions do not line up. Samples past `samples_per_pixel` start a new
+/// round over the same strata.
+pub struct StratifiedSampler {
+    seed: u64,
+    samples: u32,
+    index: u32,
+    dimension: u32,
+}
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, samples_per_pixel: u32) -> Self {
+        Self { seed, samples: samples_per_pixel.max(1), index: 0, dimension: 0 }
+    }
+
+    /// Stratum of the current sample among `strata`, and a hash for its jitter.
+    fn stratum(&self, strata: u32) -> (u32, u64) {
+        let round = self.index / self.samples;
+        let key = hash(&[self.seed, self.dimension as u64, round as u64]);
+        let stratum = permute(self.index % self.samples, strata, key as u32);
+
+        (stratum, hash(&[key, self.index as u64]))
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, index: u32) {
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn random(&mut self) -> f64 {
+        let (stratum, jitter) = self.stratum(self.samples);
+        self.dimension += 1;
+
+        (stratum as f64 + to_unit(jitter)) / self.samples as f64
+    }
+
+    fn random_2d(&mut self) -> (f64, f64) {
+        // A grid of at least `samples` cells, as square as possible.
+        let nx = ((self.samples as f64).sqrt() as u32).max(1);
+        let ny = self.samples.div_ceil(nx);
+        let (stratum, jitter) = self.stratum(nx * ny);
+        self.dimension += 2;
+
+        (
+            ((stratum % nx) as f64 + to_unit(jitter)) / nx as f64,
+            ((stratum / nx) as f64 + to_unit(mix(jitter))) / ny as f64,
+        )
+    }
+}
+
+/// Bases of the Halton dimensions; later dimensions fall back to hashing.
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+    59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Largest f64 below 1.
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+/// Radical inverse of `index` in `base`: its digits mirrored around the
+/// radix point, each passed through `permute_digit(digit, digit_index,
+/// digits_so_far)` on the way.
+fn radical_inverse(base: u64, mut index: u64, mut permute_digit: impl FnMut(u64, u64, u64) -> u64) -> f64 {
+    let mut reversed = 0u64;
+    // base^digits, the denominator of the result.
+    let mut scale = 1u64;
+    let mut digit_index = 0u64;
+
+    // Permuted zeros past the last digit of `index` matter too, so keep
+    // going until the digits fill an f64 mantissa.
+    while scale < 1 << 53 {
+        let digit = index % base;
+        index /= base;
+
+        reversed = reversed * base + permute_digit(digit, digit_index, reversed);
+        scale *= base;
+        digit_index += 1;
+    }
+
+    (reversed as f64 / scale as f64).min(ONE_MINUS_EPSILON)
+}
+
+/// Radical inverse of `index` in `base` with every digit permuted, by a
+/// permutation that depends on `seed` and the digits before it (Owen
+/// scrambling). That randomizes the sequence while keeping its
+/// stratification.
+fn scrambled_radical_inverse(base: u64, index: u64, seed: u64) -> f64 {
+    radical_inverse(base, index, |digit, digit_index, reversed| {
+        let permutation = hash(&[seed, digit_index, reversed]) as u32;
+
+        permute(digit as u32, base as u32, permutation) as u64
+    })
+}
+
+/// The Halton sequence: dimension `d` of sample `i` is the radical inverse of
+/// `i` in the `d`-th prime base, scrambled differently in every pixel.
+pub struct HaltonSampler {
+    seed: u64,
+    index: u32,
+    dimension: u32,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, index: 0, dimension: 0 }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, index: u32) {
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn random(&mut self) -> f64 {
+        let dimension = self.dimension as u64;
+        let seed = hash(&[self.seed, dimension]);
+        self.dimension += 1;
+
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => scrambled_radical_inverse(base, self.index as u64, seed),
+            None => to_unit(hash(&[seed, self.index as u64])),
+        }
+    }
+}
+
+/// Hash-based Owen scrambling of the bits of `x`: each bit is flipped
+/// depending on the bits above it (Burley, "Practical Hash-based Owen
+/// Scrambling").
+fn owen_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+
+    x.reverse_bits()
+}
+
+/// First two dimensions of the Sobol sequence, which form a (0, 2)-sequence:
+/// every power-of-two prefix is stratified in every elementary interval.
+fn sobol_2d(index: u32) -> (u32, u32) {
+    let x = index.reverse_bits();
+    let mut y = 0;
+    let mut v = 1u32 << 31;
+    let mut i = index;
+
+    while i != 0 {
+        if i & 1 != 0 {
+            y ^= v;
+        }
+        i >>= 1;
+        v ^= v >> 1;
+    }
+
+    (x, y)
+}
+
+fn unit_from_u32(bits: u32) -> f64 {
+    bits as f64 / (1u64 << 32) as f64
+}
+
+/// Owen-scrambled Sobol points, padded: each pair of dimensions is its own
+/// 2D Sobol sequence with the sample order shuffled, so pairs stay well
+/// stratified without the high dimensions of a full Sobol table.
+pub struct SobolSampler {
+    seed: u64,
+    index: u32,
+    dimension: u32,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, index: 0, dimension: 0 }
+    }
+
+    /// Seeds for the index shuffle and the scrambles of the next dimensions.
+    fn dimension_seeds(&self) -> [u32; 3] {
+        let h = hash(&[self.seed, self.dimension as u64]);
+
+        [h as u32, (h >> 32) as u32, mix(h) as u32]
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, index: u32) {
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn random(&mut self) -> f64 {
+        let [shuffle, scramble, _] = self.dimension_seeds();
+        let index = owen_scramble(self.index, shuffle);
+        self.dimension += 1;
+
+        unit_from_u32(owen_scramble(index.reverse_bits(), scramble))
+    }
+
+    fn random_2d(&mut self) -> (f64, f64) {
+        let [shuffle, scramble_x, scramble_y] = self.dimension_seeds();
+        let (x, y) = sobol_2d(owen_scramble(self.index, shuffle));
+        self.dimension += 2;
+
+        (unit_from_u32(owen_scramble(x, scramble_x)), unit_from_u32(owen_scramble(y, scramble_y)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Which of `n` equal intervals of `[0, 1)` each value falls in.
+    fn cells(values: impl IntoIterator<Item = f64>, n: u32) -> Vec<u32> {
+        let mut cells: Vec<u32> = values.into_iter().map(|v| (v * n as f64) as u32).collect();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn stratified_puts_one_sample_in_each_stratum() {
+        for samples in [1, 2, 7, 16, 25] {
+            let mut sampler = StratifiedSampler::new(42, samples);
+
+            // Every dimension, and every round past `samples`.
+            for round in 0..2 {
+                let draws: Vec<Vec<f64>> = (0..samples)
+                    .map(|i| {
+                        sampler.start_sample(round * samples + i);
+                        (0..4).map(|_| sampler.random()).collect()
+                    })
+                    .collect();
+
+                for dimension in 0..4 {
+                    let values = draws.iter().map(|draw| draw[dimension]);
+                    assert_eq!(cells(values, samples), (0..samples).collect::<Vec<_>>(), "{} spp", samples);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_2d_puts_one_sample_in_each_cell() {
+        for samples in [4u32, 6, 16] {
+            let nx = (samples as f64).sqrt() as u32;
+            let ny = samples.div_ceil(nx);
+            let mut sampler = StratifiedSampler::new(7, samples);
+
+            let mut cells: Vec<u32> = (0..samples)
+                .map(|i| {
+                    sampler.start_sample(i);
+                    let (x, y) = sampler.random_2d();
+                    (y * ny as f64) as u32 * nx + (x * nx as f64) as u32
+                })
+                .collect();
+            cells.sort();
+            cells.dedup();
+
+            assert_eq!(cells.len(), samples as usize);
+        }
+    }
+
+    #[test]
+    fn radical_inverse_matches_the_halton_sequence() {
+        // The first points of the Halton sequence in bases 2 and 3.
+        let halton_2 = [0.0, 1.0 / 2.0, 1.0 / 4.0, 3.0 / 4.0, 1.0 / 8.0, 5.0 / 8.0, 3.0 / 8.0, 7.0 / 8.0];
+        let halton_3 = [0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0 / 9.0, 4.0 / 9.0, 7.0 / 9.0, 2.0 / 9.0, 5.0 / 9.0];
+
+        for i in 0..8 {
+            assert_eq!(radical_inverse(2, i, |digit, _, _| digit), halton_2[i as usize]);
+            assert!((radical_inverse(3, i, |digit, _, _| digit) - halton_3[i as usize]).abs() < 1e-15);
+        }
+    }
+
+    #[test]
+    fn scrambled_halton_keeps_its_stratification() {
+        let mut sampler = HaltonSampler::new(1234);
+        let draws: Vec<(f64, f64)> = (0..27)
+            .map(|i| {
+                sampler.start_sample(i);
+                (sampler.random(), sampler.random())
+            })
+            .collect();
+
+        assert_eq!(cells(draws[..16].iter().map(|d| d.0), 16), (0..16).collect::<Vec<_>>());
+        assert_eq!(cells(draws.iter().map(|d| d.1), 27), (0..27).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn sobol_2d_matches_the_published_points() {
+        // The first two Sobol dimensions (Joe and Kuo's direction numbers),
+        // in index order rather than Gray code order.
+        let expected = [
+            (0.0, 0.0), (0.5, 0.5), (0.25, 0.75), (0.75, 0.25),
+            (0.125, 0.625), (0.625, 0.125), (0.375, 0.375), (0.875, 0.875),
+        ];
+
+        for (i, &point) in expected.iter().enumerate() {
+            let (x, y) = sobol_2d(i as u32);
+            assert_eq!((unit_from_u32(x), unit_from_u32(y)), point);
+        }
+    }
+
+    #[test]
+    fn scrambled_sobol_is_a_0_2_net() {
+        let mut sampler = SobolSampler::new(99);
+        let points: Vec<(f64, f64)> = (0..16)
+            .map(|i| {
+                sampler.start_sample(i);
+                sampler.random_2d()
+            })
+            .collect();
+
+        // Each of the 1x16, 2x8, 4x4, 8x2 and 16x1 grids gets one point per cell.
+        for bits in 0..=4 {
+            let (nx, ny) = (1u32 << bits, 1u32 << (4 - bits));
+            let mut cells: Vec<u32> = points
+                .iter()
+                .map(|&(x, y)| (y * ny as f64) as u32 * nx + (x * nx as f64) as u32)
+                .collect();
+            cells.sort();
+            cells.dedup();
+
+            assert_eq!(cells.len(), 16, "{}x{}", nx, ny);
+        }
+    }
+
+    #[test]
+    fn every_sampler_accepts_empty_ranges() {
+        let kinds = [SamplerKind::Independent, SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol];
+
+        for kind in kinds {
+            let mut sampler = kind.for_pixel(3, 0, 1, 2, 4);
+            sampler.start_sample(0);
+
+            assert_eq!(sampler.random_in_range(0.5, 0.5), 0.5, "{}", kind.name());
+            let value = sampler.random_in_range(-2.0, 3.0);
+            assert!((-2.0..3.0).contains(&value), "{}: {}", kind.name(), value);
+        }
+    }
+}
@@ -0,0 +1,1071 @@
+//! Declarative scene descriptions.
+//!
+//! Scenes are written in a small subset of TOML: `[camera]` and `[render]`
+//! tables plus `[[texture]]`, `[[material]]` and `[[object]]` arrays of
+//! tables. Values are numbers, strings or single-line arrays. For example:
+//!
+//! ```toml
+//! [camera]
+//! lookfrom = [13, 2, 3]
+//! lookat = [0, 0, 0]
+//! vfov = 20
+//!
+//! [[material]]
+//! name = "ground"
+//! type = "lambertian"
+//! albedo = [0.5, 0.5, 0.5]
+//!
+//! [[object]]
+//! type = "sphere"
+//! center = [0, -1000, 0]
+//! radius = 1000
+//! material = "ground"
+//! ```
+//!
+//! Object types are `sphere` (`center`, `radius`), `moving_sphere` (going
+//! from `center0` at `time0` to `center1` at `time1`, by default 0 and 1,
+//! blurred by the camera's `shutter_open`/`shutter_close`), `triangle`
+//! (`vertices`, optional `normals`), `quad` (corner `q`, edges `u` and `v`),
+//! `box` (corners `min` and `max`) and `mesh` (`file`: an OBJ path relative to the
+//! scene file; `material` is then optional and only used for faces without
+//! `usemtl`). Any object can be placed with `scale`, `rotate_x`, `rotate_y`,
+//! `rotate_z` and `translate`; objects using the same OBJ file share its
+//! geometry. Giving an object a `density` turns it into a volume of smoke or
+//! fog, usually with an `isotropic` material. Spheres, quads, boxes and
+//! triangles with a `diffuse_light` material are also sampled directly as
+//! lights.
+//!
+//! Texture types are `checker` (`scale`, `even`, `odd`, optional `mapping`:
+//! `world` or `surface`) and `image` (`file`: a PNG or PPM, optional `wrap`:
+//! `repeat`, `clamp` or `mirror`), plus the procedural `noise` (`scale`),
+//! `marble` (`scale`, `color`) and `wood` (`scale`, `light`, `dark`), which
+//! take an optional integer `seed`. A lambertian or metal `albedo`, and the
+//! checker colors, may name a texture instead of giving a color.
+
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    Point3,
+    bvh::BvhNode,
+    constant_medium::ConstantMedium,
+    hittable::Hit,
+    hittable_list::HittableList,
+    Color3,
+    material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal},
+    moving_sphere::MovingSphere,
+    obj,
+    quad::{Cuboid, Quad},
+    render::Background,
+    scenes::{CameraSettings, RenderSettings, Scene},
+    sphere::Sphere,
+    texture::{
+        Checker, ImageTexture, MarbleTexture, NoiseTexture, SolidColor, Texture, WoodTexture, WrapMode,
+    },
+    transform::Transform,
+    triangle::Triangle,
+    vec3::{Mat4, Vec3},
+};
+
+/// A problem in a scene file, pointing at the line that caused it.
+#[derive(Debug)]
+pub struct SceneError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl SceneError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self { line, message: message.into() }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Number(f64),
+    Str(String),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "a number",
+            Value::Str(_) => "a string",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+struct Entry {
+    key: String,
+    value: Value,
+    line: usize,
+}
+
+/// One `[name]` or `[[name]]` section with its key/value pairs.
+struct Table {
+    name: String,
+    is_array: bool,
+    line: usize,
+    entries: Vec<Entry>,
+}
+
+impl Table {
+    fn get(&self, key: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.key == key)
+    }
+
+    fn check_keys(&self, allowed: &[&str]) -> Result<(), SceneError> {
+        match self.entries.iter().find(|entry| !allowed.contains(&entry.key.as_str())) {
+            Some(entry) => Err(SceneError::new(entry.line, format!(
+                "unknown key `{}` in [{}] (expected one of: {})",
+                entry.key, self.name, allowed.join(", ")
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn required(&self, key: &str) -> Result<&Entry, SceneError> {
+        self.get(key).ok_or_else(|| {
+            SceneError::new(self.line, format!("[{}] is missing `{}`", self.name, key))
+        })
+    }
+
+    fn number(&self, key: &str) -> Result<Option<f64>, SceneError> {
+        match self.get(key) {
+            Some(entry) => entry.number().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn vec3(&self, key: &str) -> Result<Option<Vec3>, SceneError> {
+        match self.get(key) {
+            Some(entry) => entry.vec3().map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Entry {
+    fn type_error(&self, expected: &str) -> SceneError {
+        SceneError::new(self.line, format!(
+            "`{}` must be {}, found {}", self.key, expected, self.value.kind()
+        ))
+    }
+
+    fn number(&self) -> Result<f64, SceneError> {
+        match self.value {
+            Value::Number(n) => Ok(n),
+            _ => Err(self.type_error("a number")),
+        }
+    }
+
+    fn positive_integer(&self) -> Result<u32, SceneError> {
+        let n = self.number()?;
+
+        if n < 1.0 || n.fract() != 0.0 || n > u32::MAX as f64 {
+            return Err(SceneError::new(self.line, format!(
+                "`{}` must be a positive integer, found {}", self.key, n
+            )));
+        }
+
+        Ok(n as u32)
+    }
+
+    fn string(&self) -> Result<&str, SceneError> {
+        match &self.value {
+            Value::Str(s) => Ok(s),
+            _ => Err(self.type_error("a string")),
+        }
+    }
+
+    fn vec3(&self) -> Result<Vec3, SceneError> {
+        match &self.value {
+            Value::Array(items) if items.len() == 3 => {
+                let mut xyz = [0.0; 3];
+                for (slot, item) in xyz.iter_mut().zip(items) {
+                    match item {
+                        Value::Number(n) => *slot = *n,
+                        _ => return Err(self.type_error("an array of 3 numbers")),
+                    }
+                }
+                Ok(Vec3::new(xyz[0], xyz[1], xyz[2]))
+            }
+            _ => Err(self.type_error("an array of 3 numbers")),
+        }
+    }
+
+    /// An array of `count` points, e.g. `[[0, 0, 0], [1, 0, 0], [0, 1, 0]]`.
+    fn vec3_list(&self, count: usize) -> Result<Vec<Vec3>, SceneError> {
+        let expected = format!("an array of {} points", count);
+
+        match &self.value {
+            Value::Array(items) if items.len() == count => items.iter()
+                .map(|item| Entry { key: self.key.clone(), value: item.clone(), line: self.line }.vec3())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| self.type_error(&expected)),
+            _ => Err(self.type_error(&expected)),
+        }
+    }
+}
+
+/// Splits the source into tables. Keys before the first header end up in a
+/// table with an empty name.
+fn parse(source: &str) -> Result<Vec<Table>, SceneError> {
+    let mut tables = vec![Table {
+        name: String::new(),
+        is_array: false,
+        line: 1,
+        entries: Vec::new(),
+    }];
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line_no = index + 1;
+        let mut cursor = Cursor::new(raw_line, line_no);
+        cursor.skip_whitespace();
+
+        if cursor.at_end_of_line() {
+            continue;
+        }
+
+        if cursor.eat('[') {
+            let is_array = cursor.eat('[');
+            let name = cursor.bare_key()?;
+            cursor.expect(']')?;
+            if is_array {
+                cursor.expect(']')?;
+            }
+            cursor.finish()?;
+
+            if !is_array && tables.iter().any(|t| t.name == name && !t.is_array) {
+                return Err(SceneError::new(line_no, format!("table [{}] is defined twice", name)));
+            }
+
+            tables.push(Table { name, is_array, line: line_no, entries: Vec::new() });
+            continue;
+        }
+
+        let key = cursor.bare_key()?;
+        cursor.expect('=')?;
+        let value = cursor.value()?;
+        cursor.finish()?;
+
+        let table = tables.last_mut().unwrap();
+        if table.get(&key).is_some() {
+            return Err(SceneError::new(line_no, format!("duplicate key `{}`", key)));
+        }
+        table.entries.push(Entry { key, value, line: line_no });
+    }
+
+    Ok(tables)
+}
+
+struct Cursor<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(text: &'a str, line: usize) -> Self {
+        Self { chars: text.chars().peekable(), line }
+    }
+
+    fn error(&self, message: impl Into<String>) -> SceneError {
+        SceneError::new(self.line, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.chars.peek(), Some(c) if c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn at_end_of_line(&mut self) -> bool {
+        matches!(self.chars.peek(), None | Some('#'))
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&expected) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SceneError> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`", expected)))
+        }
+    }
+
+    /// Only whitespace or a comment may follow a complete statement.
+    fn finish(&mut self) -> Result<(), SceneError> {
+        self.skip_whitespace();
+        if self.at_end_of_line() {
+            Ok(())
+        } else {
+            let rest: String = self.chars.clone().collect();
+            Err(self.error(format!("unexpected `{}`", rest.trim())))
+        }
+    }
+
+    fn bare_key(&mut self) -> Result<String, SceneError> {
+        self.skip_whitespace();
+        let mut key = String::new();
+
+        while let Some(&c) = self.chars.peek() {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                key.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+
+        if key.is_empty() {
+            Err(self.error("expected a key"))
+        } else {
+            Ok(key)
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, SceneError> {
+        self.skip_whitespace();
+
+        match self.chars.peek() {
+            Some('"') => self.string(),
+            Some('[') => self.array(),
+            Some(_) => self.scalar(),
+            None => Err(self.error("expected a value")),
+        }
+    }
+
+    fn string(&mut self) -> Result<Value, SceneError> {
+        self.chars.next();
+        let mut s = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(Value::Str(s)),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some(c) => return Err(self.error(format!("unknown escape `\\{}`", c))),
+                    None => return Err(self.error("unterminated string")),
+                },
+                Some(c) => s.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, SceneError> {
+        self.chars.next();
+        let mut items = Vec::new();
+
+        loop {
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            if self.chars.peek().is_none() {
+                return Err(self.error("unterminated array (arrays must fit on one line)"));
+            }
+
+            items.push(self.value()?);
+
+            if !self.eat(',') {
+                self.expect(']')?;
+                return Ok(Value::Array(items));
+            }
+        }
+    }
+
+    fn scalar(&mut self) -> Result<Value, SceneError> {
+        let mut word = String::new();
+
+        while let Some(&c) = self.chars.peek() {
+            if c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_') {
+                word.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+
+        match word.replace('_', "").parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(Value::Number(n)),
+            _ => Err(self.error(format!("invalid value `{}`", word))),
+        }
+    }
+}
+
+fn load_camera(table: &Table, camera: &mut CameraSettings) -> Result<(), SceneError> {
+    table.check_keys(&[
+        "lookfrom", "lookat", "vup", "vfov", "aperture", "focus_dist", "shutter_open", "shutter_close",
+    ])?;
+
+    if let Some(v) = table.vec3("lookfrom")? {
+        camera.lookfrom = v;
+    }
+    if let Some(v) = table.vec3("lookat")? {
+        camera.lookat = v;
+    }
+    if let Some(v) = table.vec3("vup")? {
+        camera.vup = v;
+    }
+    if let Some(entry) = table.get("vfov") {
+        let vfov = entry.number()?;
+        if vfov <= 0.0 || vfov >= 180.0 {
+            return Err(SceneError::new(entry.line, "`vfov` must be between 0 and 180 degrees"));
+        }
+        camera.vfov = vfov;
+    }
+    if let Some(n) = table.number("aperture")? {
+        camera.aperture = n;
+    }
+    match table.number("focus_dist")? {
+        Some(n) => camera.focus_dist = n,
+        // Focus on the look-at point unless told otherwise.
+        None => camera.focus_dist = (camera.lookfrom - camera.lookat).length(),
+    }
+
+    if let Some(n) = table.number("shutter_open")? {
+        camera.shutter_open = n;
+    }
+    if let Some(entry) = table.get("shutter_close") {
+        camera.shutter_close = entry.number()?;
+        if camera.shutter_close < camera.shutter_open {
+            return Err(SceneError::new(entry.line, "`shutter_close` must not be before `shutter_open`"));
+        }
+    } else {
+        camera.shutter_close = camera.shutter_open;
+    }
+
+    if (camera.lookfrom - camera.lookat).near_zero() {
+        return Err(SceneError::new(table.line, "`lookfrom` and `lookat` must differ"));
+    }
+
+    Ok(())
+}
+
+fn load_render(table: &Table, render: &mut RenderSettings, background: &mut Background) -> Result<(), SceneError> {
+    table.check_keys(&["width", "height", "aspect_ratio", "samples_per_pixel", "max_depth", "background"])?;
+
+    // Either "sky" or a solid color.
+    if let Some(entry) = table.get("background") {
+        *background = match &entry.value {
+            Value::Str(s) if s == "sky" => Background::Sky,
+            Value::Str(s) => {
+                return Err(SceneError::new(entry.line, format!(
+                    "unknown background `{}` (expected \"sky\" or a color)", s
+                )));
+            }
+            _ => Background::Solid(entry.vec3()?),
+        };
+    }
+
+    if let Some(entry) = table.get("width") {
+        render.width = Some(entry.positive_integer()?);
+    }
+    if let Some(entry) = table.get("height") {
+        render.height = Some(entry.positive_integer()?);
+    }
+    if let Some(entry) = table.get("aspect_ratio") {
+        let ratio = entry.number()?;
+        if ratio <= 0.0 {
+            return Err(SceneError::new(entry.line, "`aspect_ratio` must be positive"));
+        }
+        render.aspect_ratio = Some(ratio);
+    }
+    if let Some(entry) = table.get("samples_per_pixel") {
+        render.samples_per_pixel = Some(entry.positive_integer()?);
+    }
+    if let Some(entry) = table.get("max_depth") {
+        let depth = entry.positive_integer()?;
+        render.max_depth = Some(i32::try_from(depth).map_err(|_| {
+            SceneError::new(entry.line, format!("`max_depth` must be at most {}, found {}", i32::MAX, depth))
+        })?);
+    }
+
+    Ok(())
+}
+
+/// A color, or the name of a texture declared earlier in the file.
+fn texture_or_color(
+        entry: &Entry,
+        textures: &HashMap<String, Arc<dyn Texture>>
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+    match &entry.value {
+        Value::Str(name) => textures.get(name).cloned().ok_or_else(|| {
+            SceneError::new(entry.line, format!("unknown texture `{}`", name))
+        }),
+        _ => Ok(Arc::new(SolidColor::new(entry.vec3()?))),
+    }
+}
+
+/// The optional `seed` of a procedural texture; 0 when absent.
+fn noise_seed(table: &Table) -> Result<u64, SceneError> {
+    let entry = match table.get("seed") {
+        Some(entry) => entry,
+        None => return Ok(0),
+    };
+    let n = entry.number()?;
+
+    // Numbers are read as f64, which holds every integer below 2^53 exactly;
+    // larger ones may already have been rounded to a different seed.
+    const MAX_SEED: f64 = ((1u64 << 53) - 1) as f64;
+    if n < 0.0 || n.fract() != 0.0 || n > MAX_SEED {
+        return Err(SceneError::new(entry.line, format!(
+            "`seed` must be an integer from 0 to {}, found {}", MAX_SEED, n
+        )));
+    }
+
+    Ok(n as u64)
+}
+
+fn load_texture(
+        table: &Table,
+        textures: &HashMap<String, Arc<dyn Texture>>,
+        base_dir: &Path
+    ) -> Result<(String, Arc<dyn Texture>), SceneError> {
+    let name = table.required("name")?.string()?.to_string();
+    let kind_entry = table.required("type")?;
+
+    let texture: Arc<dyn Texture> = match kind_entry.string()? {
+        "checker" => {
+            table.check_keys(&["name", "type", "scale", "even", "odd", "mapping"])?;
+            let scale_entry = table.required("scale")?;
+            let scale = scale_entry.number()?;
+            if scale <= 0.0 {
+                return Err(SceneError::new(scale_entry.line, "`scale` must be positive"));
+            }
+            let even = texture_or_color(table.required("even")?, textures)?;
+            let odd = texture_or_color(table.required("odd")?, textures)?;
+
+            match table.get("mapping") {
+                None => Arc::new(Checker::new(scale, even, odd)),
+                Some(entry) => match entry.string()? {
+                    "world" => Arc::new(Checker::new(scale, even, odd)),
+                    "surface" => Arc::new(Checker::surface(scale, even, odd)),
+                    other => {
+                        return Err(SceneError::new(entry.line, format!(
+                            "unknown mapping `{}` (expected world or surface)", other
+                        )));
+                    }
+                },
+            }
+        }
+        "image" => {
+            table.check_keys(&["name", "type", "file", "wrap"])?;
+            let wrap = match table.get("wrap") {
+                None => WrapMode::Repeat,
+                Some(entry) => {
+                    let name = entry.string()?;
+                    WrapMode::from_name(name).ok_or_else(|| SceneError::new(entry.line, format!(
+                        "unknown wrap mode `{}` (expected repeat, clamp or mirror)", name
+                    )))?
+                }
+            };
+            let file_entry = table.required("file")?;
+            let path = base_dir.join(file_entry.string()?);
+
+            let texture = ImageTexture::load(&path, wrap).map_err(|err| {
+                SceneError::new(file_entry.line, format!("cannot load `{}`: {}", path.display(), err))
+            })?;
+            Arc::new(texture)
+        }
+        "noise" => {
+            table.check_keys(&["name", "type", "scale", "seed"])?;
+            Arc::new(NoiseTexture::new(table.number("scale")?.unwrap_or(1.0), noise_seed(table)?))
+        }
+        "marble" => {
+            table.check_keys(&["name", "type", "scale", "color", "seed"])?;
+            let scale = table.number("scale")?.unwrap_or(1.0);
+            let color = table.vec3("color")?.unwrap_or(Color3::new(1.0, 1.0, 1.0));
+            Arc::new(MarbleTexture::new(scale, color, noise_seed(table)?))
+        }
+        "wood" => {
+            table.check_keys(&["name", "type", "scale", "light", "dark", "seed"])?;
+            let scale = table.number("scale")?.unwrap_or(1.0);
+            let light = table.required("light")?.vec3()?;
+            let dark = table.required("dark")?.vec3()?;
+            Arc::new(WoodTexture::new(scale, light, dark, noise_seed(table)?))
+        }
+        other => {
+            return Err(SceneError::new(kind_entry.line, format!(
+                "unknown texture type `{}` (expected checker, image, noise, marble or wood)", other
+            )));
+        }
+    };
+
+    Ok((name, texture))
+}
+
+fn load_material(
+        table: &Table,
+        textures: &HashMap<String, Arc<dyn Texture>>
+    ) -> Result<(String, Arc<dyn Material>), SceneError> {
+    let name = table.required("name")?.string()?.to_string();
+    let kind_entry = table.required("type")?;
+
+    let material: Arc<dyn Material> = match kind_entry.string()? {
+        "lambertian" => {
+            table.check_keys(&["name", "type", "albedo"])?;
+            let albedo = texture_or_color(table.required("albedo")?, textures)?;
+            Arc::new(Lambertian::from_texture(albedo))
+        }
+        "metal" => {
+            table.check_keys(&["name", "type", "albedo", "fuzz"])?;
+            let albedo = texture_or_color(table.required("albedo")?, textures)?;
+            let fuzz = table.number("fuzz")?.unwrap_or(0.0);
+            Arc::new(Metal::from_texture(albedo, fuzz))
+        }
+        "dielectric" => {
+            table.check_keys(&["name", "type", "ir"])?;
+            let entry = table.required("ir")?;
+            let ir = entry.number()?;
+            if ir <= 0.0 {
+                return Err(SceneError::new(entry.line, "`ir` must be positive"));
+            }
+            Arc::new(Dielectric::new(ir))
+        }
+        "isotropic" => {
+            table.check_keys(&["name", "type", "albedo"])?;
+            let albedo = texture_or_color(table.required("albedo")?, textures)?;
+            Arc::new(Isotropic::from_texture(albedo))
+        }
+        "diffuse_light" => {
+            table.check_keys(&["name", "type", "emit"])?;
+            Arc::new(DiffuseLight::new(table.required("emit")?.vec3()?))
+        }
+        other => {
+            return Err(SceneError::new(kind_entry.line, format!(
+                "unknown material type `{}` (expected lambertian, metal, dielectric, isotropic or diffuse_light)", other
+            )));
+        }
+    };
+
+    Ok((name, material))
+}
+
+fn lookup_material(
+        table: &Table,
+        materials: &HashMap<String, Arc<dyn Material>>
+    ) -> Result<Arc<dyn Material>, SceneError> {
+    let entry = table.required("material")?;
+    let name = entry.string()?;
+
+    materials.get(name).cloned().ok_or_else(|| {
+        SceneError::new(entry.line, format!("unknown material `{}`", name))
+    })
+}
+
+/// Keys every object accepts to place it with a [`Transform`].
+const TRANSFORM_KEYS: [&str; 5] = ["scale", "rotate_x", "rotate_y", "rotate_z", "translate"];
+
+/// Wraps `object` in a transform built from the object's `scale`,
+/// `rotate_x`/`rotate_y`/`rotate_z` (degrees) and `translate` keys, applied
+/// in that order.
+fn apply_transform(table: &Table, object: Arc<dyn Hit>) -> Result<Arc<dyn Hit>, SceneError> {
+    if !TRANSFORM_KEYS.iter().any(|key| table.get(key).is_some()) {
+        return Ok(object);
+    }
+
+    let mut matrix = Mat4::identity();
+
+    if let Some(entry) = table.get("scale") {
+        let factors = match entry.value {
+            Value::Number(n) => Vec3::new(n, n, n),
+            _ => entry.vec3()?,
+        };
+        if factors.x() * factors.y() * factors.z() == 0.0 {
+            return Err(SceneError::new(entry.line, "`scale` must not be zero along any axis"));
+        }
+        matrix = Mat4::scaling(factors) * matrix;
+    }
+    for (key, axis) in [
+        ("rotate_x", Vec3::new(1.0, 0.0, 0.0)),
+        ("rotate_y", Vec3::new(0.0, 1.0, 0.0)),
+        ("rotate_z", Vec3::new(0.0, 0.0, 1.0)),
+    ] {
+        if let Some(degrees) = table.number(key)? {
+            matrix = Mat4::rotation(axis, degrees) * matrix;
+        }
+    }
+    if let Some(offset) = table.vec3("translate")? {
+        matrix = Mat4::translation(offset) * matrix;
+    }
+
+    Ok(Arc::new(Transform::new(object, matrix)))
+}
+
+/// Meshes already loaded, keyed by file and fallback material, so an OBJ
+/// used by several objects is instanced instead of loaded again.
+type MeshCache = HashMap<(PathBuf, Option<String>), Arc<dyn Hit>>;
+
+fn load_object(
+        table: &Table,
+        materials: &HashMap<String, Arc<dyn Material>>,
+        base_dir: &Path,
+        mesh_cache: &mut MeshCache
+    ) -> Result<Arc<dyn Hit>, SceneError> {
+    let kind_entry = table.required("type")?;
+    let check_keys = |keys: &[&str]| table.check_keys(&[keys, &["density"], &TRANSFORM_KEYS[..]].concat());
+
+    let object: Arc<dyn Hit> = match kind_entry.string()? {
+        "sphere" => {
+            check_keys(&["type", "center", "radius", "material"])?;
+            let center: Point3 = table.required("center")?.vec3()?;
+            let radius = table.required("radius")?.number()?;
+            let material = lookup_material(table, materials)?;
+
+            Arc::new(Sphere::new(center, radius, material))
+        }
+        "moving_sphere" => {
+            check_keys(&["type", "center0", "center1", "time0", "time1", "radius", "material"])?;
+            let center0: Point3 = table.required("center0")?.vec3()?;
+            let center1: Point3 = table.required("center1")?.vec3()?;
+            let time0 = table.number("time0")?.unwrap_or(0.0);
+            let time1 = table.number("time1")?.unwrap_or(1.0);
+            let radius = table.required("radius")?.number()?;
+            let material = lookup_material(table, materials)?;
+
+            Arc::new(MovingSphere::new(center0, center1, time0, time1, radius, material))
+        }
+        "triangle" => {
+            check_keys(&["type", "vertices", "normals", "material"])?;
+            let vertices = table.required("vertices")?.vec3_list(3)?;
+            let material = lookup_material(table, materials)?;
+            let vertices = [vertices[0], vertices[1], vertices[2]];
+
+            match table.get("normals") {
+                Some(entry) => {
+                    let n = entry.vec3_list(3)?;
+                    Arc::new(Triangle::with_normals(vertices, [n[0], n[1], n[2]], material))
+                }
+                None => {
+                    let [v0, v1, v2] = vertices;
+                    Arc::new(Triangle::new(v0, v1, v2, material))
+                }
+            }
+        }
+        "mesh" => {
+            check_keys(&["type", "file", "material"])?;
+            let file_entry = table.required("file")?;
+            let path = base_dir.join(file_entry.string()?);
+            let material_name = match table.get("material") {
+                Some(entry) => Some(entry.string()?.to_string()),
+                None => None,
+            };
+            let key = (path, material_name);
+
+            match mesh_cache.get(&key) {
+                Some(object) => Arc::clone(object),
+                None => {
+                    // The material is the fallback for faces without `usemtl`.
+                    let default_material = match &key.1 {
+                        Some(_) => lookup_material(table, materials)?,
+                        None => Arc::new(Lambertian::new(Color3::new(0.5, 0.5, 0.5))),
+                    };
+
+                    let meshes: Vec<Arc<dyn Hit>> = obj::load(&key.0, default_material)
+                        .map_err(|err| SceneError::new(file_entry.line, err.to_string()))?
+                        .into_iter()
+                        .map(|obj_mesh| Arc::new(obj_mesh.mesh) as Arc<dyn Hit>)
+                        .collect();
+
+                    let object: Arc<dyn Hit> = match meshes.len() {
+                        0 => return Err(SceneError::new(file_entry.line, "the OBJ file has no faces")),
+                        1 => Arc::clone(&meshes[0]),
+                        _ => Arc::new(BvhNode::from_objects(meshes).map_err(|err| {
+                            SceneError::new(file_entry.line, err.to_string())
+                        })?),
+                    };
+                    mesh_cache.insert(key, Arc::clone(&object));
+                    object
+                }
+            }
+        }
+        "quad" => {
+            check_keys(&["type", "q", "u", "v", "material"])?;
+            let q = table.required("q")?.vec3()?;
+            let u = table.required("u")?.vec3()?;
+            let v_entry = table.required("v")?;
+            let v = v_entry.vec3()?;
+            if Vec3::cross(&u, &v).near_zero() {
+                return Err(SceneError::new(v_entry.line, "`u` and `v` must not be parallel"));
+            }
+            let material = lookup_material(table, materials)?;
+
+            Arc::new(Quad::new(q, u, v, material))
+        }
+        "box" => {
+            check_keys(&["type", "min", "max", "material"])?;
+            let a = table.required("min")?.vec3()?;
+            let b = table.required("max")?.vec3()?;
+            let material = lookup_material(table, materials)?;
+
+            Arc::new(Cuboid::new(a, b, material))
+        }
+        other => {
+            return Err(SceneError::new(kind_entry.line, format!(
+                "unknown object type `{}` (expected sphere, moving_sphere, triangle, quad, box or mesh)", other
+            )));
+        }
+    };
+
+    // A density fills the shape with a medium scattering off its material.
+    let object: Arc<dyn Hit> = match table.get("density") {
+        Some(entry) => {
+            let density = entry.number()?;
+            if density <= 0.0 {
+                return Err(SceneError::new(entry.line, "`density` must be positive"));
+            }
+            Arc::new(ConstantMedium::new(object, density, lookup_material(table, materials)?))
+        }
+        None => object,
+    };
+
+    apply_transform(table, object)
+}
+
+/// Whether the renderer should sample the object as a light: it has a
+/// `diffuse_light` material and a shape that implements [`Hit::random`].
+/// Meshes, volumes and moving spheres (whose position depends on the ray
+/// time, which light sampling does not know) are left to BSDF sampling.
+fn is_light(table: &Table, light_materials: &HashSet<String>) -> bool {
+    let material = match table.get("material").map(|entry| &entry.value) {
+        Some(Value::Str(name)) => name,
+        _ => return false,
+    };
+    let kind = match table.get("type").map(|entry| &entry.value) {
+        Some(Value::Str(kind)) => kind.as_str(),
+        _ => return false,
+    };
+
+    light_materials.contains(material)
+        && table.get("density").is_none()
+        && matches!(kind, "sphere" | "quad" | "box" | "triangle")
+}
+
+/// Builds a scene from its textual description. Files it references (such
+/// as OBJ meshes) are looked up relative to `base_dir`.
+pub fn parse_scene(source: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+    let tables = parse(source)?;
+
+    let mut camera = CameraSettings {
+        lookfrom: Point3::new(0.0, 0.0, 0.0),
+        lookat: Point3::new(0.0, 0.0, -1.0),
+        vup: Vec3::new(0.0, 1.0, 0.0),
+        vfov: 90.0,
+        aperture: 0.0,
+        focus_dist: 1.0,
+        shutter_open: 0.0,
+        shutter_close: 0.0,
+    };
+    let mut render = RenderSettings::default();
+    let mut background = Background::Sky;
+    let mut textures: HashMap<String, Arc<dyn Texture>> = HashMap::new();
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+    let mut light_materials = HashSet::new();
+    let mut mesh_cache = MeshCache::new();
+
+    // Textures may refer to textures declared before them.
+    for table in tables.iter().filter(|t| t.name == "texture") {
+        if !table.is_array {
+            return Err(SceneError::new(table.line, "textures are declared with [[texture]]"));
+        }
+        let (name, texture) = load_texture(table, &textures, base_dir)?;
+        if textures.insert(name.clone(), texture).is_some() {
+            let line = table.required("name")?.line;
+            return Err(SceneError::new(line, format!("texture `{}` is defined twice", name)));
+        }
+    }
+
+    // Materials may be declared after the objects that use them.
+    for table in tables.iter().filter(|t| t.name == "material") {
+        if !table.is_array {
+            return Err(SceneError::new(table.line, "materials are declared with [[material]]"));
+        }
+        let (name, material) = load_material(table, &textures)?;
+        if table.required("type")?.string()? == "diffuse_light" {
+            light_materials.insert(name.clone());
+        }
+        if materials.insert(name.clone(), material).is_some() {
+            let line = table.required("name")?.line;
+            return Err(SceneError::new(line, format!("material `{}` is defined twice", name)));
+        }
+    }
+
+    for table in &tables {
+        match (table.name.as_str(), table.is_array) {
+            ("", _) => {
+                if let Some(entry) = table.entries.first() {
+                    return Err(SceneError::new(entry.line, format!(
+                        "`{}` must be inside a table such as [camera] or [render]", entry.key
+                    )));
+                }
+            }
+            ("camera", false) => load_camera(table, &mut camera)?,
+            ("render", false) => load_render(table, &mut render, &mut background)?,
+            ("texture", true) | ("material", true) => {}
+            ("object", true) => {
+                let object = load_object(table, &materials, base_dir, &mut mesh_cache)?;
+                if is_light(table, &light_materials) {
+                    lights.add(object.clone());
+                }
+                world.add(object);
+            }
+            ("object", false) => {
+                return Err(SceneError::new(table.line, "objects are declared with [[object]]"));
+            }
+            (name, _) => {
+                return Err(SceneError::new(table.line, format!(
+                    "unknown table [{}] (expected camera, render, texture, material or object)", name
+                )));
+            }
+        }
+    }
+
+    Ok(Scene { world, lights, camera, render, background })
+}
+
+/// Reads and builds the scene stored at `path`.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|err| {
+        SceneError::new(0, format!("cannot read scene file: {}", err))
+    })?;
+
+    parse_scene(&source, path.parent().unwrap_or_else(|| Path::new("")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        match parse_scene(source, Path::new("")) {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn accepts_a_valid_scene() {
+        let scene = parse_scene(
+            "[render]\nwidth = 64\nmax_depth = 8\n\n[[material]]\nname = \"m\"\ntype = \"lambertian\"\n\
+             albedo = [0.5, 0.5, 0.5]\n\n[[object]]\ntype = \"sphere\"\ncenter = [0, 0, -1]\nradius = 0.5\nmaterial = \"m\"\n",
+            Path::new(""),
+        ).unwrap();
+
+        assert_eq!(scene.render.width, Some(64));
+        assert_eq!(scene.render.max_depth, Some(8));
+        assert_eq!(scene.world.hittables_vec.len(), 1);
+    }
+
+    #[test]
+    fn syntax_errors_name_their_line() {
+        assert_eq!(error("[camera]\nvfov 20\n"), "line 2: expected `=`");
+        assert_eq!(error("\n\n[render\n"), "line 3: expected `]`");
+        assert_eq!(error("[render]\nwidth = 10 20\n"), "line 2: unexpected `20`");
+        assert_eq!(error("[render]\nwidth = abc\n"), "line 2: invalid value `abc`");
+        assert_eq!(error("[[material]]\nname = \"open\n"), "line 2: unterminated string");
+        assert_eq!(
+            error("[camera]\nlookfrom = [1, 2,\n"),
+            "line 2: unterminated array (arrays must fit on one line)"
+        );
+    }
+
+    #[test]
+    fn structural_errors_name_their_line() {
+        assert_eq!(error("[render]\nwidth = 1\nwidth = 2\n"), "line 3: duplicate key `width`");
+        assert_eq!(error("[render]\n[camera]\n[render]\n"), "line 3: table [render] is defined twice");
+        assert_eq!(error("width = 1\n"), "line 1: `width` must be inside a table such as [camera] or [render]");
+        assert_eq!(
+            error("# comment\n[lights]\n"),
+            "line 2: unknown table [lights] (expected camera, render, texture, material or object)"
+        );
+        assert_eq!(
+            error("[render]\nsamples = 4\n"),
+            "line 2: unknown key `samples` in [render] (expected one of: width, height, aspect_ratio, \
+             samples_per_pixel, max_depth, background)"
+        );
+    }
+
+    #[test]
+    fn value_errors_name_their_line() {
+        assert_eq!(error("[render]\nwidth = \"wide\"\n"), "line 2: `width` must be a number, found a string");
+        assert_eq!(error("[render]\n\nheight = 1.5\n"), "line 3: `height` must be a positive integer, found 1.5");
+        assert_eq!(error("[camera]\nvfov = 180\n"), "line 2: `vfov` must be between 0 and 180 degrees");
+        assert_eq!(
+            error("[[object]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"nope\"\n"),
+            "line 5: unknown material `nope`"
+        );
+    }
+
+    #[test]
+    fn noise_seeds_must_be_exact_in_f64() {
+        let texture = |seed: &str| format!("[[texture]]\nname = \"n\"\ntype = \"noise\"\nseed = {}\n", seed);
+
+        assert!(parse_scene(&texture("9007199254740991"), Path::new("")).is_ok());
+        assert!(parse_scene(&texture("4294967296"), Path::new("")).is_ok());
+        assert_eq!(
+            error(&texture("9007199254740993")),
+            "line 4: `seed` must be an integer from 0 to 9007199254740991, found 9007199254740992"
+        );
+        assert_eq!(error(&texture("-1")), "line 4: `seed` must be an integer from 0 to 9007199254740991, found -1");
+    }
+
+    #[test]
+    fn max_depth_must_fit_in_i32() {
+        assert_eq!(
+            error("[render]\nmax_depth = 3000000000\n"),
+            "line 2: `max_depth` must be at most 2147483647, found 3000000000"
+        );
+        assert!(parse_scene("[render]\nmax_depth = 2147483647\n", Path::new("")).is_ok());
+    }
+
+    #[test]
+    fn only_sampleable_emitters_become_lights() {
+        let scene = parse_scene(
+            "[[material]]\nname = \"lamp\"\ntype = \"diffuse_light\"\nemit = [4, 4, 4]\n\n\
+             [[object]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"lamp\"\n\n\
+             [[object]]\ntype = \"box\"\nmin = [0, 0, 0]\nmax = [1, 1, 1]\nmaterial = \"lamp\"\n\n\
+             [[object]]\ntype = \"triangle\"\nvertices = [[0, 0, 0], [1, 0, 0], [0, 1, 0]]\nmaterial = \"lamp\"\n\n\
+             [[object]]\ntype = \"moving_sphere\"\ncenter0 = [0, 0, 0]\ncenter1 = [1, 0, 0]\nradius = 1\nmaterial = \"lamp\"\n\n\
+             [[object]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"lamp\"\ndensity = 0.5\n",
+            Path::new(""),
+        ).unwrap();
+
+        assert_eq!(scene.world.hittables_vec.len(), 5);
+        assert_eq!(scene.lights.hittables_vec.len(), 3);
+    }
+}
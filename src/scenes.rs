@@ -0,0 +1,462 @@
+use std::sync::Arc;
+
+use crate::{
+    Color3,
+    Point3,
+    vec3::Vec3,
+    hittable_list::HittableList,
+    camera::Camera,
+    sampler::{IndependentSampler, Sampler},
+    constant_medium::ConstantMedium,
+    hittable::Hit,
+    material::{DiffuseLight, Lambertian, Dielectric, Material, Metal},
+    quad::{Cuboid, Quad},
+    transform::Transform,
+    vec3::Mat4,
+    render::Background,
+    moving_sphere::MovingSphere,
+    sphere::Sphere,
+    texture::{Checker, MarbleTexture, WoodTexture}
+};
+
+/// The `Camera::new` parameters that belong to a scene; the aspect ratio
+/// comes from the output resolution instead.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraSettings {
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vup: Vec3,
+    pub vfov: f64,
+    pub aperture: f64,
+    pub focus_dist: f64,
+    /// Times the shutter opens and closes; equal for no motion blur.
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+
+impl CameraSettings {
+    pub fn build(&self, aspect_ratio: f64) -> Camera {
+        Camera::new(
+            self.lookfrom,
+            self.lookat,
+            self.vup,
+            self.vfov,
+            aspect_ratio,
+            self.aperture,
+            self.focus_dist
+        ).with_shutter(self.shutter_open, self.shutter_close)
+    }
+}
+
+/// Render settings a scene can suggest; command-line flags take precedence.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderSettings {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub aspect_ratio: Option<f64>,
+    pub samples_per_pixel: Option<u32>,
+    pub max_depth: Option<i32>,
+}
+
+pub struct Scene {
+    pub world: HittableList,
+    /// Emitters the renderer samples directly; each must also be in `world`.
+    pub lights: HittableList,
+    pub camera: CameraSettings,
+    pub render: RenderSettings,
+    pub background: Background,
+}
+
+/// Builds a built-in scene; `seed` drives the layout of the random ones.
+pub fn by_name(name: &str, seed: u64) -> Option<Scene> {
+    match name {
+        "random" => Some(random_scene(&mut IndependentSampler::new(seed))),
+        "bouncing-spheres" => Some(bouncing_spheres(&mut IndependentSampler::new(seed))),
+        "three-spheres" => Some(three_spheres()),
+        "checkered-spheres" => Some(checkered_spheres()),
+        "perlin-spheres" => Some(perlin_spheres(seed)),
+        "simple-light" => Some(simple_light()),
+        "cornell-box" => Some(cornell_box()),
+        "cornell-smoke" => Some(cornell_smoke()),
+        _ => None,
+    }
+}
+
+pub fn random_scene(sampler: &mut dyn Sampler) -> Scene {
+    random_spheres(sampler, false)
+}
+
+/// The random scene with its diffuse spheres bouncing up while the shutter
+/// is open, for motion blur.
+pub fn bouncing_spheres(sampler: &mut dyn Sampler) -> Scene {
+    random_spheres(sampler, true)
+}
+
+fn random_spheres(sampler: &mut dyn Sampler, bouncing: bool) -> Scene {
+    let mut world = HittableList::new();
+    let ground_material = Lambertian::new(Color3::new(0.5, 0.5, 0.5));
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(ground_material)
+    )));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_material = sampler.random();
+            let center = Point3::new(
+                a as f64 + 0.9 * sampler.random(),
+                0.2,
+                b as f64 + 0.9 * sampler.random()
+            );
+
+            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                if choose_material < 0.8 {
+                    // diffuse
+                    let albedo = Color3::random_vec3(sampler) * Color3::random_vec3(sampler);
+                    let sphere_material = Lambertian::new(albedo);
+
+                    if bouncing {
+                        let center1 = center + Vec3::new(0.0, sampler.random_in_range(0.0, 0.5), 0.0);
+                        world.add(Arc::new(MovingSphere::new(
+                            center,
+                            center1,
+                            0.0,
+                            1.0,
+                            0.2,
+                            Arc::new(sphere_material)
+                        )));
+                    } else {
+                        world.add(Arc::new(Sphere::new(
+                            center,
+                            0.2,
+                            Arc::new(sphere_material)
+                        )));
+                    }
+                } else if choose_material < 0.95 {
+                    // metal
+                    let albedo = Color3::random_vec3_in_range(sampler, 0.5, 1.0);
+                    let fuzz = sampler.random_in_range(0.0, 0.5);
+                    let sphere_material = Metal::new(albedo, fuzz);
+
+                    world.add(Arc::new(Sphere::new(
+                        center,
+                        0.2,
+                        Arc::new(sphere_material)
+                    )));
+                } else {
+                    // glass
+                    let sphere_material = Dielectric::new(1.5);
+
+                    world.add(Arc::new(Sphere::new(
+                        center,
+                        0.2,
+                        Arc::new(sphere_material)
+                    )));
+                }
+            }
+        }
+    }
+
+    let material1 = Dielectric::new(1.5);
+    let material2 = Lambertian::new(Color3::new(0.4, 0.2, 0.1));
+    let material3 = Metal::new(Color3::new(0.7, 0.6, 0.5), 0.0);
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(material1)
+    )));
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-4.0, 1.0, 0.0),
+        1.0,
+        Arc::new(material2)
+    )));
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(4.0, 1.0, 0.0),
+        1.0,
+        Arc::new(material3)
+    )));
+
+    Scene {
+        world,
+        lights: HittableList::new(),
+        camera: CameraSettings {
+            lookfrom: Point3::new(13.0, 2.0, 3.0),
+            lookat: Point3::new(0.0, 0.0, 0.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 20.0,
+            aperture: 0.1,
+            focus_dist: 10.0,
+            shutter_open: 0.0,
+            shutter_close: if bouncing { 1.0 } else { 0.0 },
+        },
+        render: RenderSettings::default(),
+        background: Background::Sky,
+    }
+}
+
+/// Ground plus the glass, diffuse and metal spheres, without the random field.
+pub fn three_spheres() -> Scene {
+    let mut world = HittableList::new();
+
+    let material_ground = Lambertian::new(Color3::new(0.8, 0.8, 0.0));
+    let material_center = Lambertian::new(Color3::new(0.1, 0.2, 0.5));
+    let material_left = Arc::new(Dielectric::new(1.5));
+    let material_right = Metal::new(Color3::new(0.8, 0.6, 0.2), 0.0);
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -100.5, -1.0),
+        100.0,
+        Arc::new(material_ground)
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 0.0, -1.0),
+        0.5,
+        Arc::new(material_center)
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-1.0, 0.0, -1.0),
+        0.5,
+        material_left.clone()
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-1.0, 0.0, -1.0),
+        -0.45,
+        material_left
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(1.0, 0.0, -1.0),
+        0.5,
+        Arc::new(material_right)
+    )));
+
+    let lookfrom = Point3::new(3.0, 3.0, 2.0);
+    let lookat = Point3::new(0.0, 0.0, -1.0);
+
+    Scene {
+        world,
+        lights: HittableList::new(),
+        camera: CameraSettings {
+            lookfrom,
+            lookat,
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 20.0,
+            aperture: 2.0,
+            focus_dist: (lookfrom - lookat).length(),
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        },
+        render: RenderSettings::default(),
+        background: Background::Sky,
+    }
+}
+
+/// Two large spheres sharing one solid checker texture.
+pub fn checkered_spheres() -> Scene {
+    let mut world = HittableList::new();
+
+    let checker = Arc::new(Checker::from_colors(
+        0.32,
+        Color3::new(0.2, 0.3, 0.1),
+        Color3::new(0.9, 0.9, 0.9)
+    ));
+    let material = Arc::new(Lambertian::from_texture(checker));
+
+    world.add(Arc::new(Sphere::new(Point3::new(0.0, -10.0, 0.0), 10.0, material.clone())));
+    world.add(Arc::new(Sphere::new(Point3::new(0.0, 10.0, 0.0), 10.0, material)));
+
+    Scene {
+        world,
+        lights: HittableList::new(),
+        camera: CameraSettings {
+            lookfrom: Point3::new(13.0, 2.0, 3.0),
+            lookat: Point3::new(0.0, 0.0, 0.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 20.0,
+            aperture: 0.0,
+            focus_dist: 10.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        },
+        render: RenderSettings::default(),
+        background: Background::Sky,
+    }
+}
+
+/// A marble sphere on a wooden ground; `seed` picks the noise.
+pub fn perlin_spheres(seed: u64) -> Scene {
+    let mut world = HittableList::new();
+
+    let wood = Arc::new(WoodTexture::new(
+        1.0,
+        Color3::new(0.75, 0.55, 0.3),
+        Color3::new(0.45, 0.25, 0.1),
+        seed
+    ));
+    let marble = Arc::new(MarbleTexture::new(4.0, Color3::new(1.0, 1.0, 1.0), seed));
+
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::from_texture(wood))
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 2.0, 0.0),
+        2.0,
+        Arc::new(Lambertian::from_texture(marble))
+    )));
+
+    Scene {
+        world,
+        lights: HittableList::new(),
+        camera: CameraSettings {
+            lookfrom: Point3::new(13.0, 2.0, 3.0),
+            lookat: Point3::new(0.0, 1.0, 0.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 25.0,
+            aperture: 0.0,
+            focus_dist: 10.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        },
+        render: RenderSettings::default(),
+        background: Background::Sky,
+    }
+}
+
+/// A diffuse sphere lit only by a spherical area light, against a black sky.
+pub fn simple_light() -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = Arc::new(Lambertian::new(Color3::new(0.5, 0.5, 0.5)));
+    let orange = Arc::new(Lambertian::new(Color3::new(0.8, 0.4, 0.1)));
+    let light = Arc::new(DiffuseLight::new(Color3::new(4.0, 4.0, 4.0)));
+    let lamp = Arc::new(Sphere::new(Point3::new(0.0, 7.0, 0.0), 2.0, light));
+
+    world.add(Arc::new(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground)));
+    world.add(Arc::new(Sphere::new(Point3::new(0.0, 2.0, 0.0), 2.0, orange)));
+    world.add(lamp.clone());
+
+    let mut lights = HittableList::new();
+    lights.add(lamp);
+
+    Scene {
+        world,
+        lights,
+        camera: CameraSettings {
+            lookfrom: Point3::new(26.0, 3.0, 6.0),
+            lookat: Point3::new(0.0, 2.0, 0.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 20.0,
+            aperture: 0.0,
+            focus_dist: 10.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        },
+        render: RenderSettings::default(),
+        background: Background::Solid(Color3::new(0.0, 0.0, 0.0)),
+    }
+}
+
+/// The empty Cornell box, lit by `light` on the ceiling. Returns the room,
+/// the light alone and the room's white material.
+fn cornell_room(light: Quad) -> (HittableList, HittableList, Arc<dyn Material>) {
+    let mut world = HittableList::new();
+
+    let red = Arc::new(Lambertian::new(Color3::new(0.65, 0.05, 0.05)));
+    let white: Arc<dyn Material> = Arc::new(Lambertian::new(Color3::new(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::new(Color3::new(0.12, 0.45, 0.15)));
+
+    world.add(Arc::new(Quad::new(Point3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), green)));
+    world.add(Arc::new(Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), red)));
+    let light: Arc<dyn Hit> = Arc::new(light);
+    world.add(light.clone());
+    world.add(Arc::new(Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone())));
+    world.add(Arc::new(Quad::new(Point3::new(555.0, 555.0, 555.0), Vec3::new(-555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -555.0), white.clone())));
+    world.add(Arc::new(Quad::new(Point3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white.clone())));
+
+    let mut lights = HittableList::new();
+    lights.add(light);
+
+    (world, lights, white)
+}
+
+/// The tall and the short box of the Cornell box, in place.
+fn cornell_boxes(material: Arc<dyn Material>) -> (Arc<dyn Hit>, Arc<dyn Hit>) {
+    let y_axis = Vec3::new(0.0, 1.0, 0.0);
+    let box1 = Arc::new(Cuboid::new(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 330.0, 165.0), material.clone()));
+    let box2 = Arc::new(Cuboid::new(Point3::new(0.0, 0.0, 0.0), Point3::new(165.0, 165.0, 165.0), material));
+
+    (
+        Arc::new(Transform::new(
+            box1,
+            Mat4::translation(Vec3::new(265.0, 0.0, 295.0)) * Mat4::rotation(y_axis, 15.0)
+        )),
+        Arc::new(Transform::new(
+            box2,
+            Mat4::translation(Vec3::new(130.0, 0.0, 65.0)) * Mat4::rotation(y_axis, -18.0)
+        )),
+    )
+}
+
+fn cornell_scene(world: HittableList, lights: HittableList) -> Scene {
+    Scene {
+        world,
+        lights,
+        camera: CameraSettings {
+            lookfrom: Point3::new(278.0, 278.0, -800.0),
+            lookat: Point3::new(278.0, 278.0, 0.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov: 40.0,
+            aperture: 0.0,
+            focus_dist: 10.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        },
+        render: RenderSettings {
+            width: Some(600),
+            aspect_ratio: Some(1.0),
+            ..RenderSettings::default()
+        },
+        background: Background::Solid(Color3::new(0.0, 0.0, 0.0)),
+    }
+}
+
+/// The Cornell box: five walls, a ceiling light and two boxes.
+pub fn cornell_box() -> Scene {
+    let light = Arc::new(DiffuseLight::new(Color3::new(15.0, 15.0, 15.0)));
+    let (mut world, lights, white) = cornell_room(Quad::new(
+        Point3::new(343.0, 554.0, 332.0),
+        Vec3::new(-130.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -105.0),
+        light
+    ));
+
+    let (box1, box2) = cornell_boxes(white);
+    world.add(box1);
+    world.add(box2);
+
+    cornell_scene(world, lights)
+}
+
+/// The Cornell box with its two boxes turned into dark and light smoke,
+/// under a larger, dimmer light.
+pub fn cornell_smoke() -> Scene {
+    let light = Arc::new(DiffuseLight::new(Color3::new(7.0, 7.0, 7.0)));
+    let (mut world, lights, white) = cornell_room(Quad::new(
+        Point3::new(113.0, 554.0, 127.0),
+        Vec3::new(330.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 305.0),
+        light
+    ));
+
+    let (box1, box2) = cornell_boxes(white);
+    world.add(Arc::new(ConstantMedium::from_color(box1, 0.01, Color3::new(0.0, 0.0, 0.0))));
+    world.add(Arc::new(ConstantMedium::from_color(box2, 0.01, Color3::new(1.0, 1.0, 1.0))));
+
+    cornell_scene(world, lights)
+}
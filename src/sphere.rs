@@ -1,63 +1,135 @@
-use std::rc::Rc;
+use std::{f64::consts::PI, sync::Arc};
 
 use crate::{
     Point3,
+    aabb::Aabb,
     vec3::Vec3,
     material::Material,
-    hittable::{Hit, HitRecord}
+    ray::Ray,
+    hittable::{Hit, HitRecord},
+    pdf::Onb,
+    sampler::Sampler
 };
 
 pub struct Sphere {
     pub center: Point3,
     pub radius: f64,
     // pub material: Box<dyn Material>,
-    pub material: Rc<dyn Material>,
+    pub material: Arc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: Point3, radius: f64, material: Rc<dyn Material>) -> Self {
+    pub fn new(center: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
         Self {
             center,
             radius,
             material,
         }
     }
+
+    /// Surface coordinates of a point `p` on the unit sphere: `u` is the
+    /// angle around the y axis starting from -x, `v` the angle from -y to +y,
+    /// both scaled to `[0, 1]`.
+    pub(crate) fn sphere_uv(p: &Point3) -> (f64, f64) {
+        let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
 }
 
-impl Hit for Sphere {
-    fn hit(&self, ray: &crate::ray::Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = *ray.origin() - self.center;
-        let a = ray.direction().length_squared();
-        let half_b = Vec3::dot(&oc, ray.direction());
-        let c = oc.length_squared() - self.radius.powi(2);
-        let discriminant = half_b*half_b - a*c;
-
-        if discriminant < 0.0 {
-            return None;
-        } else {
-            let sqrtd = discriminant.sqrt();
-            let mut root = (-half_b - sqrtd) / a;
+/// Intersects `ray` with the sphere of `center` and `radius`; shared by the
+/// static and the moving sphere.
+pub(crate) fn hit_sphere(
+        center: Point3,
+        radius: f64,
+        material: &Arc<dyn Material>,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64
+    ) -> Option<HitRecord> {
+    let oc = *ray.origin() - center;
+    let a = ray.direction().length_squared();
+    let half_b = Vec3::dot(&oc, ray.direction());
+    let c = oc.length_squared() - radius.powi(2);
+    let discriminant = half_b*half_b - a*c;
+
+    if discriminant < 0.0 {
+        None
+    } else {
+        let sqrtd = discriminant.sqrt();
+        let mut root = (-half_b - sqrtd) / a;
 
+        if root < t_min || root > t_max {
+            root = (-half_b + sqrtd) / a;
             if root < t_min || root > t_max {
-                root = (-half_b + sqrtd) / a;
-                if root < t_min || root > t_max {
-                    return None;
-                }
+                return None;
             }
+        }
+
+        let mut hit_record = HitRecord::new();
+
+        hit_record.t = root;
+        hit_record.p = ray.at(hit_record.t);
+
+        // let outward_normal = (hit_record.p - center).multiply_coef(1.0/radius);
+        let outward_normal = (hit_record.p - center) / radius;
+
+        hit_record.set_face_normal(ray, &outward_normal);
+        (hit_record.u, hit_record.v) = Sphere::sphere_uv(&((hit_record.p - center) / radius.abs()));
+        // hit_record.material = dyn_clone::clone_box(&*self.material);
+        hit_record.material = Arc::clone(material);
+
+        Some(hit_record)
+    }
+}
+
+impl Hit for Sphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, _sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        hit_sphere(self.center, self.radius, &self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Hollow spheres use a negative radius.
+        let r = Vec3::new(self.radius.abs(), self.radius.abs(), self.radius.abs());
+
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
 
-            let mut hit_record = HitRecord::new();
+    /// Uniform over the cone of directions the sphere subtends, or over all
+    /// directions from inside it.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let ray = Ray::new(*origin, *direction);
+        if hit_sphere(self.center, self.radius, &self.material, &ray, 0.001, f64::INFINITY).is_none() {
+            return 0.0;
+        }
 
-            hit_record.t = root;
-            hit_record.p = ray.at(hit_record.t);
+        let distance_squared = (self.center - *origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return 1.0 / (4.0 * PI);
+        }
 
-            // let outward_normal = (hit_record.p - self.center).multiply_coef(1.0/self.radius);
-            let outward_normal = (hit_record.p - self.center) / self.radius;
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
 
-            hit_record.set_face_normal(ray, &outward_normal);
-            // hit_record.material = dyn_clone::clone_box(&*self.material);
-            hit_record.material = Rc::clone(&self.material);
+        1.0 / solid_angle
+    }
 
-            Some(hit_record)
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let direction = self.center - *origin;
+        let distance_squared = direction.length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return Some(Vec3::random_unit_vector(sampler));
         }
+
+        // Uniform direction inside the cone, around +z.
+        let (r1, r2) = sampler.random_2d();
+        let z = 1.0 + r2 * ((1.0 - radius_squared / distance_squared).sqrt() - 1.0);
+        let phi = 2.0 * PI * r1;
+        let sin_theta = (1.0 - z * z).sqrt();
+
+        Some(Onb::new(&direction).local(&Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z)))
     }
 }
@@ -0,0 +1,399 @@
+use std::{fs, io, path::Path, sync::Arc};
+
+use crate::{Color3, Point3, color, image, perlin::Perlin, png, sampler::IndependentSampler};
+
+/// Color that varies over a surface, looked up with the `(u, v)` surface
+/// coordinates and position of a hit point.
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color3;
+}
+
+pub struct SolidColor {
+    pub color: Color3,
+}
+
+impl SolidColor {
+    pub fn new(color: Color3) -> Self {
+        Self { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color3 {
+        self.color
+    }
+}
+
+/// Where a checker pattern is laid out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CheckerSpace {
+    /// Cubes of the given size in world space.
+    World(f64),
+    /// Squares of the given size in `(u, v)`.
+    Surface(f64),
+}
+
+/// Alternates between two textures in a checkerboard.
+pub struct Checker {
+    space: CheckerSpace,
+    pub even: Arc<dyn Texture>,
+    pub odd: Arc<dyn Texture>,
+}
+
+impl Checker {
+    /// Solid 3D checker made of `scale`-sized cubes in world space.
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self { space: CheckerSpace::World(scale), even, odd }
+    }
+
+    /// Checker painted on the surface, with squares `scale` wide in `(u, v)`.
+    pub fn surface(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self { space: CheckerSpace::Surface(scale), even, odd }
+    }
+
+    pub fn from_colors(scale: f64, even: Color3, odd: Color3) -> Self {
+        Self::new(scale, Arc::new(SolidColor::new(even)), Arc::new(SolidColor::new(odd)))
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color3 {
+        let cell_sum = match self.space {
+            CheckerSpace::World(scale) => {
+                (p.x() / scale).floor() + (p.y() / scale).floor() + (p.z() / scale).floor()
+            }
+            CheckerSpace::Surface(scale) => (u / scale).floor() + (v / scale).floor(),
+        };
+
+        if (cell_sum as i64).rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// How texture coordinates outside `[0, 1]` are mapped back onto the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "repeat" => Some(WrapMode::Repeat),
+            "clamp" => Some(WrapMode::Clamp),
+            "mirror" => Some(WrapMode::Mirror),
+            _ => None,
+        }
+    }
+
+    /// Maps texel index `i` into `0..n`.
+    fn apply(self, i: i64, n: i64) -> usize {
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Clamp => i.clamp(0, n - 1),
+            WrapMode::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m < n { m } else { 2 * n - 1 - m }
+            }
+        };
+
+        i as usize
+    }
+}
+
+/// Texture backed by an image, sampled with bilinear filtering.
+///
+/// `(0, 0)` is the bottom-left corner of the image and `(1, 1)` the top-right.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    /// Linear colors, row-major with the top row first.
+    texels: Vec<Color3>,
+    pub wrap: WrapMode,
+}
+
+impl ImageTexture {
+    /// Builds a texture from 8-bit RGB data, decoded from sRGB to linear.
+    pub fn from_rgb8(width: u32, height: u32, rgb: &[u8], wrap: WrapMode) -> Self {
+        assert!(width > 0 && height > 0, "texture images must not be empty");
+        assert_eq!(rgb.len(), width as usize * height as usize * 3, "pixel buffer does not match image size");
+
+        let decode = |b: u8| color::srgb_to_linear(b as f64 / 255.0);
+        let texels = rgb
+            .chunks(3)
+            .map(|c| Color3::new(decode(c[0]), decode(c[1]), decode(c[2])))
+            .collect();
+
+        Self { width: width as usize, height: height as usize, texels, wrap }
+    }
+
+    /// Loads a PNG or PPM (P3/P6) file, detected from its contents.
+    pub fn load<P: AsRef<Path>>(path: P, wrap: WrapMode) -> io::Result<Self> {
+        let data = fs::read(path)?;
+
+        let (width, height, rgb) = if data.starts_with(b"\x89PNG") {
+            png::read_png(&data)?
+        } else if data.starts_with(b"P3") || data.starts_with(b"P6") {
+            image::read_ppm(&data)?
+        } else {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported image format (expected PNG or PPM)"));
+        };
+
+        if width == 0 || height == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "image is empty"));
+        }
+
+        Ok(Self::from_rgb8(width, height, &rgb, wrap))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color3 {
+        let x = self.wrap.apply(x, self.width as i64);
+        let y = self.wrap.apply(y, self.height as i64);
+
+        self.texels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color3 {
+        // Texel centers sit at half-integer positions.
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+        let bottom = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+/// Grayscale Perlin noise; `scale` is the spatial frequency.
+pub struct NoiseTexture {
+    noise: Perlin,
+    pub scale: f64,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64, seed: u64) -> Self {
+        Self { noise: Perlin::new(&mut IndependentSampler::new(seed)), scale }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color3 {
+        Color3::new(1.0, 1.0, 1.0) * 0.5 * (1.0 + self.noise.noise(&(*p * self.scale)))
+    }
+}
+
+/// Marble: bands of `color` along z, distorted by turbulence.
+pub struct MarbleTexture {
+    noise: Perlin,
+    pub scale: f64,
+    pub color: Color3,
+}
+
+impl MarbleTexture {
+    pub fn new(scale: f64, color: Color3, seed: u64) -> Self {
+        Self { noise: Perlin::new(&mut IndependentSampler::new(seed)), scale, color }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color3 {
+        let phase = self.scale * p.z() + 10.0 * self.noise.turbulence(p, 7);
+
+        self.color * 0.5 * (1.0 + phase.sin())
+    }
+}
+
+/// Wood: rings around the y axis, `scale` rings per unit, blending from
+/// `light` to `dark` across each ring and perturbed by noise.
+pub struct WoodTexture {
+    noise: Perlin,
+    pub scale: f64,
+    pub light: Color3,
+    pub dark: Color3,
+}
+
+impl WoodTexture {
+    pub fn new(scale: f64, light: Color3, dark: Color3, seed: u64) -> Self {
+        Self { noise: Perlin::new(&mut IndependentSampler::new(seed)), scale, light, dark }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color3 {
+        let radius = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        let rings = radius * self.scale + 0.5 * self.noise.turbulence(p, 4);
+        let t = rings - rings.floor();
+
+        self.light * (1.0 - t) + self.dark * t
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(texture: &dyn Texture, u: f64, v: f64) -> f64 {
+        texture.value(u, v, &Point3::new(0.0, 0.0, 0.0)).x()
+    }
+
+    /// One row with a black texel on the left and a white one on the right.
+    fn black_white(wrap: WrapMode) -> ImageTexture {
+        ImageTexture::from_rgb8(2, 1, &[0, 0, 0, 255, 255, 255], wrap)
+    }
+
+    fn assert_close(actual: f64, expected: f64, what: &str) {
+        assert!((actual - expected).abs() < 1e-9, "{}: {} != {}", what, actual, expected);
+    }
+
+    #[test]
+    fn world_checker_alternates_by_cube_parity() {
+        let checker = Checker::from_colors(1.0, Color3::new(0.0, 0.0, 0.0), Color3::new(1.0, 1.0, 1.0));
+        let at = |x, y, z| checker.value(0.0, 0.0, &Point3::new(x, y, z)).x();
+
+        assert_eq!(at(0.5, 0.5, 0.5), 0.0);
+        assert_eq!(at(1.5, 0.5, 0.5), 1.0);
+        assert_eq!(at(1.5, 1.5, 0.5), 0.0);
+        assert_eq!(at(1.5, 1.5, 1.5), 1.0);
+        // Cells keep alternating across zero rather than mirroring.
+        assert_eq!(at(-0.5, 0.5, 0.5), 1.0);
+        assert_eq!(at(-0.5, -0.5, 0.5), 0.0);
+    }
+
+    #[test]
+    fn surface_checker_alternates_in_uv() {
+        let even = Arc::new(SolidColor::new(Color3::new(0.0, 0.0, 0.0)));
+        let odd = Arc::new(SolidColor::new(Color3::new(1.0, 1.0, 1.0)));
+        let checker = Checker::surface(0.5, even, odd);
+
+        assert_eq!(gray(&checker, 0.25, 0.25), 0.0);
+        assert_eq!(gray(&checker, 0.75, 0.25), 1.0);
+        assert_eq!(gray(&checker, 0.25, 0.75), 1.0);
+        assert_eq!(gray(&checker, 0.75, 0.75), 0.0);
+        // Position is ignored.
+        assert_eq!(checker.value(0.25, 0.25, &Point3::new(1.5, 0.0, 0.0)).x(), 0.0);
+    }
+
+    #[test]
+    fn image_filters_bilinearly_between_texel_centers() {
+        let texture = black_white(WrapMode::Clamp);
+
+        assert_close(gray(&texture, 0.25, 0.5), 0.0, "left center");
+        assert_close(gray(&texture, 0.75, 0.5), 1.0, "right center");
+        assert_close(gray(&texture, 0.5, 0.5), 0.5, "midway");
+        assert_close(gray(&texture, 0.375, 0.5), 0.25, "quarter way");
+    }
+
+    #[test]
+    fn image_v_runs_from_bottom_to_top() {
+        let texture = ImageTexture::from_rgb8(1, 2, &[255, 255, 255, 0, 0, 0], WrapMode::Clamp);
+
+        assert_close(gray(&texture, 0.5, 0.75), 1.0, "top row");
+        assert_close(gray(&texture, 0.5, 0.25), 0.0, "bottom row");
+    }
+
+    #[test]
+    fn clamp_extends_the_edge_texels() {
+        let texture = black_white(WrapMode::Clamp);
+
+        assert_close(gray(&texture, 0.0, 0.5), 0.0, "u = 0");
+        assert_close(gray(&texture, 1.0, 0.5), 1.0, "u = 1");
+        assert_close(gray(&texture, -3.0, 0.5), 0.0, "u < 0");
+        assert_close(gray(&texture, 5.0, 0.5), 1.0, "u > 1");
+        assert_close(gray(&texture, 0.75, -2.0), 1.0, "v < 0");
+        assert_close(gray(&texture, 0.75, 3.0), 1.0, "v > 1");
+    }
+
+    #[test]
+    fn repeat_tiles_the_image() {
+        let texture = black_white(WrapMode::Repeat);
+
+        // The edges blend the first and last columns.
+        assert_close(gray(&texture, 0.0, 0.5), 0.5, "u = 0");
+        assert_close(gray(&texture, 1.0, 0.5), 0.5, "u = 1");
+        for u in [0.1, 0.25, 0.6, 0.75] {
+            let expected = gray(&texture, u, 0.5);
+            assert_close(gray(&texture, u + 1.0, 0.5), expected, "u + 1");
+            assert_close(gray(&texture, u - 2.0, 0.5), expected, "u - 2");
+            assert_close(gray(&texture, u, 4.5), expected, "v + 4");
+        }
+    }
+
+    #[test]
+    fn mirror_reflects_at_the_edges() {
+        let texture = black_white(WrapMode::Mirror);
+
+        assert_close(gray(&texture, 0.0, 0.5), 0.0, "u = 0");
+        assert_close(gray(&texture, 1.0, 0.5), 1.0, "u = 1");
+        for u in [0.1, 0.25, 0.6, 0.75] {
+            let expected = gray(&texture, u, 0.5);
+            assert_close(gray(&texture, -u, 0.5), expected, "-u");
+            assert_close(gray(&texture, 2.0 - u, 0.5), expected, "2 - u");
+            assert_close(gray(&texture, u + 2.0, 0.5), expected, "u + 2");
+        }
+    }
+
+    #[test]
+    fn wrap_modes_parse_by_name() {
+        assert_eq!(WrapMode::from_name("repeat"), Some(WrapMode::Repeat));
+        assert_eq!(WrapMode::from_name("clamp"), Some(WrapMode::Clamp));
+        assert_eq!(WrapMode::from_name("mirror"), Some(WrapMode::Mirror));
+        assert_eq!(WrapMode::from_name("wrap"), None);
+    }
+
+    #[test]
+    fn marble_scales_its_color_by_a_sine_band() {
+        let color = Color3::new(0.8, 0.4, 0.2);
+        let marble = MarbleTexture::new(4.0, color, 5);
+        let same = MarbleTexture::new(4.0, color, 5);
+        let mut sampler = IndependentSampler::new(11);
+
+        for _ in 0..500 {
+            let p = Point3::random_vec3_in_range(&mut sampler, -10.0, 10.0);
+            let value = marble.value(0.0, 0.0, &p);
+            let band = value.x() / color.x();
+
+            assert!((0.0..=1.0).contains(&band), "{}", band);
+            assert_close(value.y(), band * color.y(), "green");
+            assert_close(value.z(), band * color.z(), "blue");
+            assert_eq!(value.x(), same.value(0.0, 0.0, &p).x());
+        }
+    }
+
+    #[test]
+    fn wood_blends_between_its_two_colors() {
+        let (light, dark) = (Color3::new(0.9, 0.6, 0.3), Color3::new(0.3, 0.2, 0.1));
+        let wood = WoodTexture::new(3.0, light, dark, 6);
+        let same = WoodTexture::new(3.0, light, dark, 6);
+        let mut sampler = IndependentSampler::new(12);
+
+        for _ in 0..500 {
+            let p = Point3::random_vec3_in_range(&mut sampler, -10.0, 10.0);
+            let value = wood.value(0.0, 0.0, &p);
+            let t = (light.x() - value.x()) / (light.x() - dark.x());
+
+            assert!((0.0..1.0 + 1e-9).contains(&t), "{}", t);
+            assert_close(value.y(), light.y() * (1.0 - t) + dark.y() * t, "green");
+            assert_close(value.z(), light.z() * (1.0 - t) + dark.z() * t, "blue");
+            assert_eq!(value.x(), same.value(0.0, 0.0, &p).x());
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! Output transform from the renderer's linear radiance to display-ready
+//! 8-bit sRGB: exposure, white balance, tone mapping and the sRGB transfer
+//! function, in that order.
+
+use crate::{Color3, color};
+
+/// Curves that compress linear radiance into the displayable `[0, 1]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToneMapOperator {
+    /// Cuts everything above 1.
+    #[default]
+    Clamp,
+    /// `x / (1 + x)`: never clips, but flattens highlights.
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic reference rendering.
+    Aces,
+    /// John Hable's filmic curve from Uncharted 2.
+    Hable,
+}
+
+impl ToneMapOperator {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "clamp" | "none" => Some(ToneMapOperator::Clamp),
+            "reinhard" => Some(ToneMapOperator::Reinhard),
+            "aces" => Some(ToneMapOperator::Aces),
+            "hable" | "filmic" => Some(ToneMapOperator::Hable),
+            _ => None,
+        }
+    }
+
+    /// Maps one linear channel value into `[0, 1]`.
+    pub fn apply(self, x: f64) -> f64 {
+        // Every curve has reached 1 long before the cap, which keeps the
+        // rational ones from dividing infinity by infinity.
+        let x = if x > 0.0 { x.min(1e16) } else { 0.0 };
+
+        let mapped = match self {
+            ToneMapOperator::Clamp => x,
+            ToneMapOperator::Reinhard => x / (1.0 + x),
+            ToneMapOperator::Aces => {
+                let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                (x * (a * x + b)) / (x * (c * x + d) + e)
+            }
+            ToneMapOperator::Hable => {
+                // The curve is usually fed twice the exposure and scaled so
+                // that a linear white of 11.2 maps to 1.
+                const EXPOSURE_BIAS: f64 = 2.0;
+                const WHITE: f64 = 11.2;
+                hable(x * EXPOSURE_BIAS) / hable(WHITE)
+            }
+        };
+
+        mapped.clamp(0.0, 1.0)
+    }
+}
+
+fn hable(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+type Mat3 = [[f64; 3]; 3];
+
+fn mul(a: &Mat3, b: &Mat3) -> Mat3 {
+    let mut m = [[0.0; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+
+    m
+}
+
+fn apply(m: &Mat3, v: [f64; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|i| m[i][0] * v[0] + m[i][1] * v[1] + m[i][2] * v[2])
+}
+
+/// Linear sRGB (D65) to CIE XYZ.
+const XYZ_FROM_SRGB: Mat3 = [
+    [0.4124564, 0.3575761, 0.1804375],
+    [0.2126729, 0.7151522, 0.0721750],
+    [0.0193339, 0.1191920, 0.9503041],
+];
+
+const SRGB_FROM_XYZ: Mat3 = [
+    [3.2404542, -1.5371385, -0.4985314],
+    [-0.9692660, 1.8760108, 0.0415560],
+    [0.0556434, -0.2040259, 1.0572252],
+];
+
+/// The Bradford cone response matrix, for chromatic adaptation.
+const BRADFORD: Mat3 = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+const BRADFORD_INVERSE: Mat3 = [
+    [0.9869929, -0.1470543, 0.1599627],
+    [0.4323053, 0.5183603, 0.0492912],
+    [-0.0085287, 0.0400428, 0.9684867],
+];
+
+const D65_XYZ: [f64; 3] = [0.95047, 1.0, 1.08883];
+
+/// Chromaticity of a light source with color temperature `kelvin`: the CIE
+/// daylight locus from 4000 K up, the Planckian locus below (Kang et al.).
+fn white_point_xy(kelvin: f64) -> (f64, f64) {
+    let t = kelvin.clamp(1667.0, 25000.0);
+    let (t2, t3) = (t * t, t * t * t);
+
+    if t >= 4000.0 {
+        let x = if t <= 7000.0 {
+            -4.6070e9 / t3 + 2.9678e6 / t2 + 0.09911e3 / t + 0.244063
+        } else {
+            -2.0064e9 / t3 + 1.9018e6 / t2 + 0.24748e3 / t + 0.237040
+        };
+
+        (x, -3.0 * x * x + 2.87 * x - 0.275)
+    } else {
+        let x = -0.2661239e9 / t3 - 0.2343589e6 / t2 + 0.8776956e3 / t + 0.179910;
+        let y = if t <= 2222.0 {
+            -1.1063814 * x * x * x - 1.34811020 * x * x + 2.18555832 * x - 0.20219683
+        } else {
+            -0.9549476 * x * x * x - 1.37418593 * x * x + 2.09137015 * x - 0.16748867
+        };
+
+        (x, y)
+    }
+}
+
+/// Linear sRGB matrix that makes a white lit by a `kelvin` light source look
+/// neutral, adapting it to D65 with the Bradford transform.
+fn white_balance_matrix(kelvin: f64) -> Mat3 {
+    let (x, y) = white_point_xy(kelvin);
+    let source = apply(&BRADFORD, [x / y, 1.0, (1.0 - x - y) / y]);
+    let target = apply(&BRADFORD, D65_XYZ);
+    let scale = [
+        [target[0] / source[0], 0.0, 0.0],
+        [0.0, target[1] / source[1], 0.0],
+        [0.0, 0.0, target[2] / source[2]],
+    ];
+
+    let adapt = mul(&BRADFORD_INVERSE, &mul(&scale, &BRADFORD));
+    mul(&SRGB_FROM_XYZ, &mul(&adapt, &XYZ_FROM_SRGB))
+}
+
+/// Settings of the output transform that LDR encoders apply to the linear
+/// framebuffer. HDR formats skip it and store the raw linear values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputTransform {
+    /// Exposure adjustment in stops: each one doubles the brightness.
+    pub exposure: f64,
+    pub operator: ToneMapOperator,
+    /// Color temperature of the light that should come out neutral, in
+    /// kelvin, or `None` to leave the colors alone.
+    pub white_balance: Option<f64>,
+    matrix: Option<Mat3>,
+}
+
+impl OutputTransform {
+    pub fn new(exposure: f64, operator: ToneMapOperator, white_balance: Option<f64>) -> Self {
+        Self {
+            exposure,
+            operator,
+            white_balance,
+            matrix: white_balance.map(white_balance_matrix),
+        }
+    }
+
+    /// Display-referred color in `[0, 1]`, sRGB encoded.
+    pub fn apply(&self, linear: Color3) -> Color3 {
+        let mut rgb = [linear.x(), linear.y(), linear.z()].map(|c| c * self.exposure.exp2());
+        if let Some(matrix) = &self.matrix {
+            rgb = apply(matrix, rgb);
+        }
+        let [r, g, b] = rgb.map(|c| color::linear_to_srgb(self.operator.apply(c)));
+
+        Color3::new(r, g, b)
+    }
+
+    pub fn to_rgb8(&self, linear: Color3) -> [u8; 3] {
+        let encoded = self.apply(linear);
+
+        [encoded.x(), encoded.y(), encoded.z()].map(|c| (c * 255.0).round() as u8)
+    }
+}
+
+impl Default for OutputTransform {
+    /// No exposure change, white balance or tone curve beyond clipping.
+    fn default() -> Self {
+        Self::new(0.0, ToneMapOperator::Clamp, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERATORS: [ToneMapOperator; 4] = [
+        ToneMapOperator::Clamp,
+        ToneMapOperator::Reinhard,
+        ToneMapOperator::Aces,
+        ToneMapOperator::Hable,
+    ];
+
+    #[test]
+    fn black_stays_black() {
+        for operator in OPERATORS {
+            assert!(operator.apply(0.0).abs() < 1e-15, "{:?}", operator);
+            assert!(operator.apply(-1.0).abs() < 1e-15, "{:?}", operator);
+            assert!(operator.apply(f64::NAN).abs() < 1e-15, "{:?}", operator);
+        }
+    }
+
+    #[test]
+    fn curves_saturate_at_one() {
+        for operator in OPERATORS {
+            assert_eq!(operator.apply(1e20), 1.0, "{:?}", operator);
+            assert_eq!(operator.apply(f64::INFINITY), 1.0, "{:?}", operator);
+        }
+        assert!((ToneMapOperator::Reinhard.apply(1e6) - 1.0).abs() < 1e-5);
+        // Narkowicz's fit crosses 1 a little above 7.
+        assert!(ToneMapOperator::Aces.apply(7.0) < 1.0);
+        assert_eq!(ToneMapOperator::Aces.apply(8.0), 1.0);
+    }
+
+    #[test]
+    fn curves_are_monotonic() {
+        for operator in OPERATORS {
+            let values: Vec<f64> = (0..200).map(|i| operator.apply(i as f64 * 0.05)).collect();
+            assert!(values.windows(2).all(|w| w[0] <= w[1]), "{:?}", operator);
+        }
+        assert_eq!(ToneMapOperator::Reinhard.apply(1.0), 0.5);
+    }
+
+    #[test]
+    fn white_balance_at_d65_is_the_identity() {
+        // The daylight locus passes within a few kelvin of D65 (6504 K).
+        let matrix = white_balance_matrix(6500.0);
+        for (i, row) in matrix.iter().enumerate() {
+            for (j, &value) in row.iter().enumerate() {
+                let identity = if i == j { 1.0 } else { 0.0 };
+                assert!((value - identity).abs() < 2e-3, "{:?}", matrix);
+            }
+        }
+    }
+
+    #[test]
+    fn white_balance_neutralizes_its_light() {
+        // Under a warm light, white looks orange; balancing for it restores gray.
+        let (x, y) = white_point_xy(3000.0);
+        let warm = apply(&SRGB_FROM_XYZ, [x / y, 1.0, (1.0 - x - y) / y]);
+        assert!(warm[0] > 1.5 * warm[2], "{:?}", warm);
+
+        let balanced = apply(&white_balance_matrix(3000.0), warm);
+        assert!((balanced[0] - balanced[1]).abs() < 1e-3 && (balanced[1] - balanced[2]).abs() < 1e-3, "{:?}", balanced);
+    }
+
+    #[test]
+    fn default_transform_only_encodes() {
+        let transform = OutputTransform::default();
+
+        assert_eq!(transform.to_rgb8(Color3::new(0.0, 0.5, 1.0)), [0, 188, 255]);
+        assert_eq!(transform.to_rgb8(Color3::new(2.0, -1.0, 0.0031308)), [255, 0, 10]);
+        assert_eq!(OutputTransform::new(1.0, ToneMapOperator::Clamp, None).to_rgb8(Color3::new(0.25, 0.25, 0.25)), [188; 3]);
+    }
+}
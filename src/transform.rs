@@ -0,0 +1,231 @@
+use std::sync::Arc;
+
+use crate::{
+    Point3,
+    aabb::Aabb,
+    hittable::{Hit, HitRecord},
+    ray::Ray,
+    sampler::Sampler,
+    vec3::{Mat4, Vec3},
+};
+
+/// Places a shared object in the world through an affine matrix.
+///
+/// Several `Transform`s can wrap the same `Arc`, which instances the object
+/// without copying its geometry.
+pub struct Transform {
+    object: Arc<dyn Hit>,
+    matrix: Mat4,
+    inverse: Mat4,
+    /// Absolute determinant of the inverse's linear part: how it scales volumes.
+    inverse_determinant: f64,
+    bbox: Option<Aabb>,
+}
+
+impl Transform {
+    /// Panics if `matrix` is not invertible.
+    pub fn new(object: Arc<dyn Hit>, matrix: Mat4) -> Self {
+        let inverse = matrix.inverse().expect("transform matrix must be invertible");
+        let bbox = object.bounding_box().map(|b| transform_box(&b, &matrix));
+        let [x, y, z] = [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)]
+            .map(|axis| inverse.transform_vector(&axis));
+        let inverse_determinant = Vec3::dot(&x, &Vec3::cross(&y, &z)).abs();
+
+        Self { object, matrix, inverse, inverse_determinant, bbox }
+    }
+
+    pub fn translate(object: Arc<dyn Hit>, offset: Vec3) -> Self {
+        Self::new(object, Mat4::translation(offset))
+    }
+
+    /// Rotates by `degrees` around the given axis through the origin.
+    pub fn rotate(object: Arc<dyn Hit>, axis: Vec3, degrees: f64) -> Self {
+        Self::new(object, Mat4::rotation(axis, degrees))
+    }
+
+    pub fn scale(object: Arc<dyn Hit>, factors: Vec3) -> Self {
+        Self::new(object, Mat4::scaling(factors))
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+}
+
+/// Box around the eight transformed corners of `bbox`.
+fn transform_box(bbox: &Aabb, matrix: &Mat4) -> Aabb {
+    let mut result: Option<Aabb> = None;
+
+    for i in 0..8 {
+        let corner = Point3::new(
+            if i & 1 == 0 { bbox.minimum.x() } else { bbox.maximum.x() },
+            if i & 2 == 0 { bbox.minimum.y() } else { bbox.maximum.y() },
+            if i & 4 == 0 { bbox.minimum.z() } else { bbox.maximum.z() },
+        );
+        let p = matrix.transform_point(&corner);
+        let point_box = Aabb::new(p, p);
+
+        result = Some(match result {
+            Some(b) => Aabb::surrounding_box(&b, &point_box),
+            None => point_box,
+        });
+    }
+
+    result.unwrap()
+}
+
+impl Hit for Transform {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        // The direction is not renormalized, so t means the same in both spaces.
+        let object_ray = Ray::with_time(
+            self.inverse.transform_point(ray.origin()),
+            self.inverse.transform_vector(ray.direction()),
+            ray.time()
+        );
+
+        let mut hit_record = self.object.hit(&object_ray, t_min, t_max, sampler)?;
+
+        hit_record.p = self.matrix.transform_point(&hit_record.p);
+        // front_face stays valid: the dot product of the ray direction and the
+        // normal is unchanged by the inverse transpose.
+        hit_record.normal = Vec3::unit_vector(self.inverse.transform_normal(&hit_record.normal));
+
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+
+    /// The object's density, times the Jacobian of the map from world to
+    /// object directions: |det M^-1| / |M^-1 w|^3 for a unit direction w.
+    /// It is 1 unless the matrix scales the axes differently.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let object_direction = self.inverse.transform_vector(direction);
+        let object_pdf = self.object.pdf_value(&self.inverse.transform_point(origin), &object_direction);
+        if object_pdf <= 0.0 {
+            return 0.0;
+        }
+        let stretch = direction.length() / object_direction.length();
+
+        object_pdf * self.inverse_determinant * stretch * stretch * stretch
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let object_origin = self.inverse.transform_point(origin);
+        let direction = self.object.random(&object_origin, sampler)?;
+
+        Some(self.matrix.transform_vector(&direction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color3, material::Lambertian, quad::Quad, sampler::IndependentSampler, sphere::Sphere};
+
+    const EPS: f64 = 1e-9;
+
+    fn unit_sphere() -> Arc<dyn Hit> {
+        Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Arc::new(Lambertian::new(Color3::new(0.5, 0.5, 0.5)))))
+    }
+
+    fn assert_vec_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < EPS, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn hits_the_placed_object_in_world_space() {
+        let matrix = Mat4::translation(Vec3::new(1.0, 2.0, 3.0))
+            * Mat4::rotation(Vec3::new(0.0, 1.0, 0.0), 90.0)
+            * Mat4::scaling(Vec3::new(2.0, 2.0, 2.0));
+        let placed = Transform::new(unit_sphere(), matrix);
+
+        let ray = Ray::new(Point3::new(11.0, 2.0, 3.0), Vec3::new(-1.0, 0.0, 0.0));
+        let rec = placed.hit(&ray, 0.001, f64::INFINITY, &mut IndependentSampler::new(0)).unwrap();
+
+        assert!((rec.t - 8.0).abs() < EPS);
+        assert_vec_eq(rec.p, Point3::new(3.0, 2.0, 3.0));
+        assert_vec_eq(rec.normal, Vec3::new(1.0, 0.0, 0.0));
+        assert!(rec.front_face);
+
+        let bbox = placed.bounding_box().unwrap();
+        assert_vec_eq(bbox.minimum, Point3::new(-1.0, 0.0, 1.0));
+        assert_vec_eq(bbox.maximum, Point3::new(3.0, 4.0, 5.0));
+    }
+
+    #[test]
+    fn normals_use_the_inverse_transpose() {
+        // The ellipsoid x²/4 + y² + z² = 1.
+        let placed = Transform::scale(unit_sphere(), Vec3::new(2.0, 1.0, 1.0));
+        let ray = Ray::new(Point3::new(1.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = placed.hit(&ray, 0.001, f64::INFINITY, &mut IndependentSampler::new(0)).unwrap();
+
+        let y = (0.75f64).sqrt();
+        assert_vec_eq(rec.p, Point3::new(1.0, y, 0.0));
+        assert_vec_eq(rec.normal, Vec3::unit_vector(Vec3::new(0.25, y, 0.0)));
+    }
+
+    #[test]
+    fn instances_share_geometry() {
+        let sphere = unit_sphere();
+        let left = Transform::translate(Arc::clone(&sphere), Vec3::new(-5.0, 0.0, 0.0));
+        let right = Transform::translate(Arc::clone(&sphere), Vec3::new(5.0, 0.0, 0.0));
+
+        assert_eq!(Arc::strong_count(&sphere), 3);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(left.hit(&ray, 0.001, f64::INFINITY, &mut IndependentSampler::new(0)).is_none());
+        assert!((right.hit(&ray, 0.001, f64::INFINITY, &mut IndependentSampler::new(0)).unwrap().t - 4.0).abs() < EPS);
+    }
+
+    /// A unit quad facing the origin, stretched to 3 x 0.5 and tilted.
+    fn stretched_quad() -> Transform {
+        let quad = Quad::new(
+            Point3::new(-0.5, -0.5, 1.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Arc::new(Lambertian::new(Color3::new(0.5, 0.5, 0.5)))
+        );
+        let matrix = Mat4::rotation(Vec3::new(1.0, 0.0, 0.0), 20.0) * Mat4::scaling(Vec3::new(3.0, 0.5, 1.5));
+
+        Transform::new(Arc::new(quad), matrix)
+    }
+
+    #[test]
+    fn stretched_light_density_integrates_to_one() {
+        let light = stretched_quad();
+        let origin = Point3::new(0.1, 0.0, 0.0);
+        let mut sampler = IndependentSampler::new(3);
+        let n = 400_000;
+
+        let sum: f64 = (0..n).map(|_| light.pdf_value(&origin, &Vec3::random_unit_vector(&mut sampler))).sum();
+        let integral = sum * 4.0 * std::f64::consts::PI / n as f64;
+
+        assert!((integral - 1.0).abs() < 0.03, "{}", integral);
+    }
+
+    #[test]
+    fn stretched_light_density_matches_its_samples() {
+        let light = stretched_quad();
+        let origin = Point3::new(0.1, 0.0, 0.0);
+        let mut sampler = IndependentSampler::new(8);
+        let directions: Vec<Vec3> = (0..400_000)
+            .map(|_| Vec3::unit_vector(light.random(&origin, &mut sampler).unwrap()))
+            .collect();
+
+        // The share of samples in a narrow cone, per unit solid angle,
+        // estimates the density along its axis.
+        let cos_half_angle = 0.05f64.cos();
+        let cone = 2.0 * std::f64::consts::PI * (1.0 - cos_half_angle);
+        // Points on the quad, before the transform.
+        for target in [Point3::new(0.0, 0.0, 1.0), Point3::new(0.3, 0.2, 1.0), Point3::new(-0.4, -0.3, 1.0)] {
+            let axis = Vec3::unit_vector(light.matrix().transform_point(&target) - origin);
+            let inside = directions.iter().filter(|d| Vec3::dot(d, &axis) >= cos_half_angle).count();
+            let estimate = inside as f64 / (directions.len() as f64 * cone);
+            let pdf = light.pdf_value(&origin, &axis);
+
+            assert!(pdf > 0.0);
+            assert!((estimate / pdf - 1.0).abs() < 0.06, "{:?}: {} vs {}", target, estimate, pdf);
+        }
+    }
+}
@@ -0,0 +1,429 @@
+use std::sync::Arc;
+
+use crate::{
+    Point3,
+    aabb::Aabb,
+    bvh::BvhNode,
+    hittable::{Hit, HitRecord},
+    material::Material,
+    quad::area_pdf_to_solid_angle,
+    ray::Ray,
+    sampler::Sampler,
+    vec3::Vec3,
+};
+
+const EPSILON: f64 = 1e-12;
+
+/// Möller–Trumbore ray/triangle intersection. Returns the ray parameter and
+/// the barycentric coordinates (b1, b2) of the hit relative to p1 and p2.
+fn intersect(p0: &Point3, p1: &Point3, p2: &Point3, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+    let edge1 = *p1 - *p0;
+    let edge2 = *p2 - *p0;
+    let pvec = Vec3::cross(ray.direction(), &edge2);
+    let det = Vec3::dot(&edge1, &pvec);
+
+    // The ray is parallel to the triangle's plane.
+    if det.abs() < EPSILON {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let tvec = *ray.origin() - *p0;
+    let b1 = Vec3::dot(&tvec, &pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = Vec3::cross(&tvec, &edge1);
+    let b2 = Vec3::dot(ray.direction(), &qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = Vec3::dot(&edge2, &qvec) * inv_det;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+/// Fills a hit record for a triangle hit, interpolating the vertex normals
+/// when the triangle has them.
+fn hit_record(
+        ray: &Ray,
+        (t, b1, b2): (f64, f64, f64),
+        positions: [&Point3; 3],
+        normals: Option<[&Vec3; 3]>,
+        uvs: Option<[&[f64; 2]; 3]>,
+        material: &Arc<dyn Material>
+    ) -> HitRecord {
+    let mut hit_record = HitRecord::new();
+    let b0 = 1.0 - b1 - b2;
+
+    hit_record.t = t;
+    hit_record.p = ray.at(t);
+    // Texture coordinates when the vertices have them, barycentrics otherwise.
+    (hit_record.u, hit_record.v) = match uvs {
+        Some([uv0, uv1, uv2]) => (
+            b0 * uv0[0] + b1 * uv1[0] + b2 * uv2[0],
+            b0 * uv0[1] + b1 * uv1[1] + b2 * uv2[1],
+        ),
+        None => (b1, b2),
+    };
+
+    // Which side was hit is decided by the geometric normal, so smooth
+    // shading can't flip a triangle inside out.
+    let geometric_normal = Vec3::unit_vector(Vec3::cross(
+        &(*positions[1] - *positions[0]),
+        &(*positions[2] - *positions[0])
+    ));
+    hit_record.set_face_normal(ray, &geometric_normal);
+
+    if let Some([n0, n1, n2]) = normals {
+        let shading_normal = Vec3::unit_vector(*n0 * b0 + *n1 * b1 + *n2 * b2);
+        hit_record.normal = if hit_record.front_face {
+            shading_normal
+        } else {
+            -shading_normal
+        };
+    }
+
+    hit_record.material = Arc::clone(material);
+    hit_record
+}
+
+fn triangle_box(p0: &Point3, p1: &Point3, p2: &Point3) -> Aabb {
+    let bbox = Aabb::surrounding_box(
+        &Aabb::new(*p0, *p0),
+        &Aabb::surrounding_box(&Aabb::new(*p1, *p1), &Aabb::new(*p2, *p2))
+    );
+
+    bbox.padded(1e-4)
+}
+
+pub struct Triangle {
+    pub vertices: [Point3; 3],
+    /// Optional per-vertex normals for smooth shading.
+    pub normals: Option<[Vec3; 3]>,
+    pub material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, material: Arc<dyn Material>) -> Self {
+        Self {
+            vertices: [v0, v1, v2],
+            normals: None,
+            material,
+        }
+    }
+
+    pub fn with_normals(vertices: [Point3; 3], normals: [Vec3; 3], material: Arc<dyn Material>) -> Self {
+        Self {
+            vertices,
+            normals: Some(normals),
+            material,
+        }
+    }
+}
+
+impl Hit for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, _sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        let [p0, p1, p2] = &self.vertices;
+        let hit = intersect(p0, p1, p2, ray, t_min, t_max)?;
+        let normals = self.normals.as_ref().map(|[n0, n1, n2]| [n0, n1, n2]);
+
+        Some(hit_record(ray, hit, [p0, p1, p2], normals, None, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let [p0, p1, p2] = &self.vertices;
+
+        Some(triangle_box(p0, p1, p2))
+    }
+
+    /// Uniform over the triangle's area, converted to solid angle.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let [p0, p1, p2] = &self.vertices;
+        let ray = Ray::new(*origin, *direction);
+        let hit = match intersect(p0, p1, p2, &ray, 0.001, f64::INFINITY) {
+            Some(hit) => hit,
+            None => return 0.0,
+        };
+
+        let hit_record = hit_record(&ray, hit, [p0, p1, p2], None, None, &self.material);
+        let normal = Vec3::cross(&(*p1 - *p0), &(*p2 - *p0));
+        let area = 0.5 * normal.length();
+
+        area_pdf_to_solid_angle(&hit_record, direction, &Vec3::unit_vector(normal), area)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let [p0, p1, p2] = &self.vertices;
+        // Folding the unit square onto the triangle keeps the points uniform.
+        let (r1, r2) = sampler.random_2d();
+        let s = r1.sqrt();
+        let point = *p0 * (1.0 - s) + *p1 * (s * (1.0 - r2)) + *p2 * (s * r2);
+
+        Some(point - *origin)
+    }
+}
+
+/// Vertex and index buffers shared by all triangles of a mesh.
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    /// Per-vertex normals, either empty or as long as `positions`.
+    pub normals: Vec<Vec3>,
+    /// Per-vertex texture coordinates, either empty or as long as `positions`.
+    pub uvs: Vec<[f64; 2]>,
+    pub indices: Vec<[usize; 3]>,
+    pub material: Arc<dyn Material>,
+}
+
+/// One triangle of a mesh: just a handle to the shared buffers.
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    index: usize,
+}
+
+impl MeshTriangle {
+    fn positions(&self) -> [&Point3; 3] {
+        let [i0, i1, i2] = self.mesh.indices[self.index];
+
+        [&self.mesh.positions[i0], &self.mesh.positions[i1], &self.mesh.positions[i2]]
+    }
+}
+
+impl Hit for MeshTriangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, _sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        let [p0, p1, p2] = self.positions();
+        let hit = intersect(p0, p1, p2, ray, t_min, t_max)?;
+
+        let [i0, i1, i2] = self.mesh.indices[self.index];
+        let normals = if self.mesh.normals.is_empty() {
+            None
+        } else {
+            Some([&self.mesh.normals[i0], &self.mesh.normals[i1], &self.mesh.normals[i2]])
+        };
+        let uvs = if self.mesh.uvs.is_empty() {
+            None
+        } else {
+            Some([&self.mesh.uvs[i0], &self.mesh.uvs[i1], &self.mesh.uvs[i2]])
+        };
+
+        Some(hit_record(ray, hit, [p0, p1, p2], normals, uvs, &self.mesh.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let [p0, p1, p2] = self.positions();
+
+        Some(triangle_box(p0, p1, p2))
+    }
+}
+
+/// Indexed triangle mesh with a single material and its own BVH.
+pub struct TriangleMesh {
+    data: Arc<MeshData>,
+    bvh: Option<BvhNode>,
+}
+
+impl TriangleMesh {
+    /// Panics if an index is out of range or the normals or UVs are neither
+    /// empty nor as long as the positions.
+    pub fn new(data: MeshData) -> Self {
+        let vertex_count = data.positions.len();
+        assert!(
+            data.normals.is_empty() || data.normals.len() == vertex_count,
+            "a mesh needs one normal per vertex or none at all"
+        );
+        assert!(
+            data.uvs.is_empty() || data.uvs.len() == vertex_count,
+            "a mesh needs one UV per vertex or none at all"
+        );
+        assert!(
+            data.indices.iter().flatten().all(|&i| i < vertex_count),
+            "mesh index out of range"
+        );
+
+        let data = Arc::new(data);
+        let triangles: Vec<Arc<dyn Hit>> = (0..data.indices.len())
+            .map(|index| Arc::new(MeshTriangle { mesh: Arc::clone(&data), index }) as Arc<dyn Hit>)
+            .collect();
+        // Every triangle has a bounding box, so only an empty mesh has no BVH.
+        let bvh = BvhNode::from_objects(triangles).ok();
+
+        Self { data, bvh }
+    }
+
+    pub fn data(&self) -> &MeshData {
+        &self.data
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.data.indices.len()
+    }
+}
+
+impl Hit for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        self.bvh.as_ref()?.hit(ray, t_min, t_max, sampler)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match &self.bvh {
+            Some(bvh) => bvh.bounding_box(),
+            // An empty mesh can't be hit, but still needs a box to sit in a BVH.
+            None => Some(Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, 0.0))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color3, material::Lambertian, sampler::IndependentSampler};
+
+    const EPS: f64 = 1e-12;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color3::new(0.5, 0.5, 0.5)))
+    }
+
+    /// The right triangle with its right angle at the origin, in the z = 0 plane.
+    fn unit_triangle() -> Triangle {
+        Triangle::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0), material())
+    }
+
+    fn hit(object: &dyn Hit, origin: Point3, direction: Vec3) -> Option<HitRecord> {
+        object.hit(&Ray::new(origin, direction), 0.001, f64::INFINITY, &mut IndependentSampler::new(0))
+    }
+
+    fn down_at(x: f64, y: f64) -> Option<HitRecord> {
+        hit(&unit_triangle(), Point3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0))
+    }
+
+    fn assert_vec_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn hits_inside_with_barycentric_uvs() {
+        let rec = down_at(0.25, 0.5).unwrap();
+
+        assert!((rec.t - 1.0).abs() < EPS);
+        assert_vec_eq(rec.p, Point3::new(0.25, 0.5, 0.0));
+        assert!((rec.u - 0.25).abs() < EPS && (rec.v - 0.5).abs() < EPS);
+        assert!(rec.front_face);
+        assert_vec_eq(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn back_faces_flip_the_normal() {
+        let rec = hit(&unit_triangle(), Point3::new(0.25, 0.25, -1.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+
+        assert!(!rec.front_face);
+        assert_vec_eq(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn misses_outside_and_beyond_the_interval() {
+        for (x, y) in [(0.6, 0.6), (-0.1, 0.5), (0.5, -0.1), (2.0, 0.0)] {
+            assert!(down_at(x, y).is_none(), "({}, {})", x, y);
+        }
+
+        let ray = Ray::new(Point3::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(unit_triangle().hit(&ray, 0.001, 0.5, &mut IndependentSampler::new(0)).is_none());
+        assert!(hit(&unit_triangle(), Point3::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn edges_and_vertices_count_as_inside() {
+        for (x, y) in [(0.5, 0.5), (0.0, 0.5), (0.5, 0.0), (0.0, 0.0), (1.0, 0.0)] {
+            assert!(down_at(x, y).is_some(), "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn parallel_rays_miss() {
+        assert!(hit(&unit_triangle(), Point3::new(-1.0, 0.2, 0.0), Vec3::new(1.0, 0.0, 0.0)).is_none());
+        assert!(hit(&unit_triangle(), Point3::new(-1.0, 0.2, 0.5), Vec3::new(1.0, 0.3, 0.0)).is_none());
+    }
+
+    #[test]
+    fn interpolates_vertex_normals() {
+        let normals = [
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::unit_vector(Vec3::new(1.0, 0.0, 1.0)),
+            Vec3::unit_vector(Vec3::new(0.0, 1.0, 1.0)),
+        ];
+        let triangle = Triangle::with_normals(unit_triangle().vertices, normals, material());
+        let expected = Vec3::unit_vector(normals[0] * 0.25 + normals[1] * 0.25 + normals[2] * 0.5);
+
+        let front = hit(&triangle, Point3::new(0.25, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!(front.front_face);
+        assert_vec_eq(front.normal, expected);
+
+        // The side is still decided by the geometric normal.
+        let back = hit(&triangle, Point3::new(0.25, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!(!back.front_face);
+        assert_vec_eq(back.normal, -expected);
+    }
+
+    /// The unit square in the z = 0 plane as two triangles.
+    fn square_mesh(uvs: Vec<[f64; 2]>) -> TriangleMesh {
+        TriangleMesh::new(MeshData {
+            positions: vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(1.0, 1.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+            ],
+            normals: Vec::new(),
+            uvs,
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+            material: material(),
+        })
+    }
+
+    #[test]
+    fn mesh_interpolates_vertex_uvs() {
+        let mesh = square_mesh(vec![[0.2, 0.4], [1.0, 0.4], [1.0, 1.0], [0.2, 1.0]]);
+
+        // (0.75, 0.25) lies in the first triangle, with barycentrics (0.25, 0.5, 0.25).
+        let rec = hit(&mesh, Point3::new(0.75, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!((rec.u - 0.8).abs() < EPS, "{}", rec.u);
+        assert!((rec.v - 0.55).abs() < EPS, "{}", rec.v);
+    }
+
+    #[test]
+    fn mesh_triangles_share_the_buffers() {
+        let mesh = square_mesh(Vec::new());
+
+        assert_eq!(mesh.triangle_count(), 2);
+        assert_eq!(Arc::strong_count(&mesh.data), 3);
+        for (x, y) in [(0.75, 0.25), (0.25, 0.75)] {
+            let rec = hit(&mesh, Point3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0)).unwrap();
+            assert_vec_eq(rec.p, Point3::new(x, y, 0.0));
+        }
+        assert!(hit(&mesh, Point3::new(1.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0)).is_none());
+
+        // Flat along z, so padded there.
+        let bbox = mesh.bounding_box().unwrap();
+        assert_eq!([bbox.minimum.x(), bbox.minimum.y(), bbox.maximum.x(), bbox.maximum.y()], [0.0, 0.0, 1.0, 1.0]);
+        assert!(bbox.minimum.z() < 0.0 && bbox.maximum.z() > 0.0);
+    }
+
+    #[test]
+    fn empty_meshes_have_a_box_but_no_hits() {
+        let mesh = TriangleMesh::new(MeshData {
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            indices: Vec::new(),
+            material: material(),
+        });
+
+        assert!(mesh.bounding_box().is_some());
+        assert!(hit(&mesh, Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0)).is_none());
+    }
+}
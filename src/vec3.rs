@@ -1,5 +1,5 @@
-use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
-use crate::rtweekend;
+use std::ops::{Add, AddAssign, Div, Index, Mul, Neg, Sub};
+use crate::{Point3, sampler::Sampler};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Vec3 {
@@ -64,48 +64,68 @@ impl Vec3 {
         v3 / v3.length()
     }
 
-    pub fn random_vec3_in_range(min: f64, max: f64) -> Self {
+    pub fn random_vec3_in_range(sampler: &mut dyn Sampler, min: f64, max: f64) -> Self {
         Vec3::new(
-            rtweekend::random_in_range(min, max),
-            rtweekend::random_in_range(min, max),
-            rtweekend::random_in_range(min, max),
+            sampler.random_in_range(min, max),
+            sampler.random_in_range(min, max),
+            sampler.random_in_range(min, max),
         )
     }
 
-    pub fn random_vec3() -> Self {
+    pub fn random_vec3(sampler: &mut dyn Sampler) -> Self {
         Vec3::new(
-            rtweekend::random(),
-            rtweekend::random(),
-            rtweekend::random(),
+            sampler.random(),
+            sampler.random(),
+            sampler.random(),
         )
     }
 
-    pub fn random_in_unit_sphere() -> Self {
-        loop {
-            let p = Vec3::random_vec3_in_range(-1.0, 1.0);
+    // The samplers below map a fixed number of sample dimensions straight to
+    // the shape instead of rejecting points, so stratified samples stay
+    // stratified.
 
-            if p.length_squared() < 1.0 {
-                return p;
-            }
-        }
+    /// Uniformly distributed point inside the unit sphere.
+    pub fn random_in_unit_sphere(sampler: &mut dyn Sampler) -> Self {
+        // The cube root makes the density uniform in volume.
+        Self::random_unit_vector(sampler) * sampler.random().cbrt()
     }
 
-    pub fn random_unit_vector() -> Self {
-        Self::unit_vector(Self::random_in_unit_sphere())
+    /// Uniformly distributed direction.
+    pub fn random_unit_vector(sampler: &mut dyn Sampler) -> Self {
+        let (r1, r2) = sampler.random_2d();
+        let z = 1.0 - 2.0 * r1;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * r2;
+
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
     }
 
-    pub fn random_in_unit_disk() -> Self {
-        loop {
-            let p = Vec3::new(
-                rtweekend::random_in_range(-1.0, 1.0),
-                rtweekend::random_in_range(-1.0, 1.0),
-                0.0
-            );
+    /// Uniformly distributed point in the unit disk in the xy plane, using
+    /// Shirley's concentric mapping from the square.
+    pub fn random_in_unit_disk(sampler: &mut dyn Sampler) -> Self {
+        use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
 
-            if p.length_squared() < 1.0 {
-                return p;
-            }
+        let (r1, r2) = sampler.random_2d();
+        let (a, b) = (2.0 * r1 - 1.0, 2.0 * r2 - 1.0);
+        if a == 0.0 && b == 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
         }
+
+        let (r, theta) = if a.abs() > b.abs() {
+            (a, FRAC_PI_4 * (b / a))
+        } else {
+            (b, FRAC_PI_2 - FRAC_PI_4 * (a / b))
+        };
+
+        Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+    }
+
+    /// Direction in the hemisphere around +z with density cos(theta) / pi.
+    pub fn random_cosine_direction(sampler: &mut dyn Sampler) -> Self {
+        let (r1, r2) = sampler.random_2d();
+        let phi = 2.0 * std::f64::consts::PI * r1;
+
+        Vec3::new(phi.cos() * r2.sqrt(), phi.sin() * r2.sqrt(), (1.0 - r2).sqrt())
     }
 
     pub fn near_zero(&self) -> bool {
@@ -132,6 +152,19 @@ impl Vec3 {
     }
 }
 
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index out of range: {}", index),
+        }
+    }
+}
+
 impl Add for Vec3 {
     type Output = Self;
 
@@ -227,3 +260,480 @@ impl Neg for Vec3 {
         }
     }
 }
+
+/// 4x4 matrix acting on column vectors, stored row by row.
+///
+/// Points and directions are both `Vec3`; which one is meant is picked by
+/// the method: points get the translation, directions don't.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub fn identity() -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        Self::new([
+            [factors.x, 0.0, 0.0, 0.0],
+            [0.0, factors.y, 0.0, 0.0],
+            [0.0, 0.0, factors.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Counter-clockwise rotation by `degrees` around `axis` (Rodrigues' formula).
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let k = Vec3::unit_vector(axis);
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let t = 1.0 - cos;
+
+        Self::new([
+            [t * k.x * k.x + cos, t * k.x * k.y - sin * k.z, t * k.x * k.z + sin * k.y, 0.0],
+            [t * k.x * k.y + sin * k.z, t * k.y * k.y + cos, t * k.y * k.z - sin * k.x, 0.0],
+            [t * k.x * k.z - sin * k.y, t * k.y * k.z + sin * k.x, t * k.z * k.z + cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Camera-to-world matrix of a viewer at `eye` looking towards `target`.
+    ///
+    /// The camera looks down its local -z axis with +y as up, as in `Camera`.
+    pub fn look_at(eye: Point3, target: Point3, vup: Vec3) -> Self {
+        let w = Vec3::unit_vector(eye - target);
+        let u = Vec3::unit_vector(Vec3::cross(&vup, &w));
+        let v = Vec3::cross(&w, &u);
+
+        Self::new([
+            [u.x, v.x, w.x, eye.x],
+            [u.y, v.y, w.y, eye.y],
+            [u.z, v.z, w.z, eye.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = [[0.0; 4]; 4];
+
+        for (i, row) in t.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+
+        Self::new(t)
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting; `None` if singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::identity().m;
+
+        // Each pivot is judged against the largest entry of its column, so
+        // that scaling an axis up or down does not change whether the matrix
+        // inverts. The homogeneous row would swamp an absolute threshold.
+        let column_size: [f64; 4] = std::array::from_fn(|col| {
+            self.m.iter().fold(0.0f64, |max, row| max.max(row[col].abs()))
+        });
+
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() <= 1e-12 * column_size[col] {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+
+        Some(Self::new(inv))
+    }
+
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+
+        if w == 1.0 {
+            Vec3::new(x, y, z)
+        } else {
+            Vec3::new(x / w, y / w, z / w)
+        }
+    }
+
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    /// Transforms a surface normal by the transpose of `self`.
+    ///
+    /// Normals must go through the inverse transpose of the matrix applied to
+    /// the surface, so call this on the *inverse* of that matrix.
+    pub fn transform_normal(&self, n: &Vec3) -> Vec3 {
+        let m = &self.m;
+
+        Vec3::new(
+            m[0][0] * n.x + m[1][0] * n.y + m[2][0] * n.z,
+            m[0][1] * n.x + m[1][1] * n.y + m[2][1] * n.z,
+            m[0][2] * n.x + m[1][2] * n.y + m[2][2] * n.z,
+        )
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut m = [[0.0; 4]; 4];
+
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+
+        Self::new(m)
+    }
+}
+
+/// Quaternion `w + xi + yj + zk`. Unit quaternions represent rotations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quat {
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Self { w, x, y, z }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    /// Counter-clockwise rotation by `degrees` around `axis`.
+    pub fn from_axis_angle(axis: Vec3, degrees: f64) -> Self {
+        let k = Vec3::unit_vector(axis);
+        let (sin, cos) = (degrees.to_radians() / 2.0).sin_cos();
+
+        Self::new(cos, k.x * sin, k.y * sin, k.z * sin)
+    }
+
+    pub fn dot(a: &Quat, b: &Quat) -> f64 {
+        a.w * b.w + a.x * b.x + a.y * b.y + a.z * b.z
+    }
+
+    pub fn length(&self) -> f64 {
+        Quat::dot(self, self).sqrt()
+    }
+
+    pub fn normalized(&self) -> Self {
+        let len = self.length();
+
+        Self::new(self.w / len, self.x / len, self.y / len, self.z / len)
+    }
+
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// Spherical linear interpolation between two rotations along the
+    /// shorter arc; `t = 0` gives `a` and `t = 1` gives `b`.
+    pub fn slerp(a: &Quat, b: &Quat, t: f64) -> Self {
+        let mut b = *b;
+        let mut cos = Quat::dot(a, &b);
+
+        // q and -q are the same rotation; take the shorter way round.
+        if cos < 0.0 {
+            b = Self::new(-b.w, -b.x, -b.y, -b.z);
+            cos = -cos;
+        }
+
+        let (s0, s1) = if cos > 0.9995 {
+            // Nearly parallel: fall back to a normalized lerp.
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+
+        Self::new(
+            s0 * a.w + s1 * b.w,
+            s0 * a.x + s1 * b.x,
+            s0 * a.y + s1 * b.y,
+            s0 * a.z + s1 * b.z,
+        ).normalized()
+    }
+
+    /// Rotates `v` by this unit quaternion.
+    pub fn rotate(&self, v: &Vec3) -> Vec3 {
+        let p = Quat::new(0.0, v.x, v.y, v.z);
+        let r = *self * p * self.conjugate();
+
+        Vec3::new(r.x, r.y, r.z)
+    }
+
+    /// Rotation matrix of this unit quaternion.
+    pub fn to_mat4(&self) -> Mat4 {
+        let Quat { w, x, y, z } = *self;
+
+        Mat4::new([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y), 0.0],
+            [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x), 0.0],
+            [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+impl Mul for Quat {
+    type Output = Self;
+
+    /// Hamilton product: the rotation `rhs` followed by `self`.
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS: f64 = 1e-9;
+
+    fn assert_vec_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < EPS, "{:?} != {:?}", a, b);
+    }
+
+    fn assert_mat_eq(a: &Mat4, b: &Mat4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((a.m[i][j] - b.m[i][j]).abs() < EPS, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    /// Checks the upper 3x3 block is a proper rotation: orthonormal rows,
+    /// determinant +1.
+    fn assert_orthonormal(m: &Mat4) {
+        let rows: Vec<Vec3> = (0..3).map(|i| Vec3::new(m.m[i][0], m.m[i][1], m.m[i][2])).collect();
+
+        for i in 0..3 {
+            for j in 0..3 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((Vec3::dot(&rows[i], &rows[j]) - expected).abs() < EPS);
+            }
+        }
+        assert!((Vec3::dot(&Vec3::cross(&rows[0], &rows[1]), &rows[2]) - 1.0).abs() < EPS);
+    }
+
+    fn sample_transform() -> Mat4 {
+        Mat4::translation(Vec3::new(1.0, -2.0, 3.5))
+            * Mat4::rotation(Vec3::new(1.0, 2.0, -0.5), 37.0)
+            * Mat4::scaling(Vec3::new(2.0, 0.5, -3.0))
+    }
+
+    #[test]
+    fn inverse_round_trip() {
+        let m = sample_transform();
+        let inv = m.inverse().unwrap();
+
+        assert_mat_eq(&(m * inv), &Mat4::identity());
+        assert_mat_eq(&(inv * m), &Mat4::identity());
+
+        let p = Vec3::new(0.3, -7.0, 2.0);
+        assert_vec_eq(inv.transform_point(&m.transform_point(&p)), p);
+        assert_vec_eq(inv.transform_vector(&m.transform_vector(&p)), p);
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert!(Mat4::scaling(Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn tiny_and_huge_scales_still_invert() {
+        for factor in [1e-9, 1e-14, 1e9, 1e14] {
+            let m = Mat4::scaling(Vec3::new(factor, factor, factor)) * Mat4::rotation(Vec3::new(1.0, 1.0, 0.0), 30.0);
+            let inv = m.inverse().unwrap_or_else(|| panic!("scale {} reported as singular", factor));
+
+            assert_mat_eq(&(m * inv), &Mat4::identity());
+        }
+
+        // A large translation next to a small scale is not singular either.
+        let m = Mat4::translation(Vec3::new(1e6, -1e6, 1e6)) * Mat4::scaling(Vec3::new(1e-4, 1e-4, 1e-4));
+        assert_mat_eq(&(m * m.inverse().unwrap()), &Mat4::identity());
+    }
+
+    #[test]
+    fn singular_matrices_are_rejected_at_any_scale() {
+        for factor in [1e-9, 1.0, 1e9] {
+            assert!(Mat4::scaling(Vec3::new(factor, 0.0, factor)).inverse().is_none());
+
+            // Two equal rows, up to rounding.
+            let mut m = Mat4::scaling(Vec3::new(factor, factor, factor));
+            m.m[1] = [factor, factor * (1.0 + 1e-15), 0.0, 0.0];
+            m.m[0] = [factor, factor, 0.0, 0.0];
+            assert!(m.inverse().is_none(), "scale {}", factor);
+        }
+        assert!(Mat4::new([[0.0; 4]; 4]).inverse().is_none());
+    }
+
+    #[test]
+    fn transpose_is_an_involution() {
+        let m = sample_transform();
+
+        assert_mat_eq(&m.transpose().transpose(), &m);
+        assert_eq!(m.transpose().m[0][3], m.m[3][0]);
+    }
+
+    #[test]
+    fn points_translate_but_vectors_do_not() {
+        let m = Mat4::translation(Vec3::new(1.0, 2.0, 3.0));
+        let v = Vec3::new(1.0, 1.0, 1.0);
+
+        assert_vec_eq(m.transform_point(&v), Vec3::new(2.0, 3.0, 4.0));
+        assert_vec_eq(m.transform_vector(&v), v);
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scale() {
+        let m = Mat4::scaling(Vec3::new(4.0, 1.0, 1.0)) * Mat4::rotation(Vec3::new(0.0, 0.0, 1.0), 30.0);
+        let inv = m.inverse().unwrap();
+        let tangent = Vec3::new(1.0, -1.0, 0.0);
+        let normal = Vec3::new(1.0, 1.0, 0.0);
+
+        let t = m.transform_vector(&tangent);
+        let n = inv.transform_normal(&normal);
+        assert!(Vec3::dot(&t, &n).abs() < EPS);
+    }
+
+    #[test]
+    fn rotation_is_orthonormal() {
+        let m = Mat4::rotation(Vec3::new(-1.0, 3.0, 0.2), 123.0);
+
+        assert_orthonormal(&m);
+        assert_mat_eq(&m.inverse().unwrap(), &m.transpose());
+        assert_vec_eq(
+            Mat4::rotation(Vec3::new(0.0, 0.0, 1.0), 90.0).transform_vector(&Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn look_at_is_orthonormal_and_maps_the_view_axis() {
+        let eye = Vec3::new(13.0, 2.0, 3.0);
+        let target = Vec3::new(0.0, 0.0, 0.0);
+        let m = Mat4::look_at(eye, target, Vec3::new(0.0, 1.0, 0.0));
+
+        assert_orthonormal(&m);
+        assert_vec_eq(m.transform_point(&Vec3::new(0.0, 0.0, 0.0)), eye);
+        assert_vec_eq(
+            m.transform_vector(&Vec3::new(0.0, 0.0, -1.0)),
+            Vec3::unit_vector(target - eye),
+        );
+    }
+
+    #[test]
+    fn quat_matches_matrix_rotation() {
+        let axis = Vec3::new(0.5, -1.0, 2.0);
+        let q = Quat::from_axis_angle(axis, 71.0);
+        let m = Mat4::rotation(axis, 71.0);
+        let v = Vec3::new(3.0, 1.0, -2.0);
+
+        assert!((q.length() - 1.0).abs() < EPS);
+        assert_mat_eq(&q.to_mat4(), &m);
+        assert_orthonormal(&q.to_mat4());
+        assert_vec_eq(q.rotate(&v), m.transform_vector(&v));
+    }
+
+    #[test]
+    fn quat_product_composes_rotations() {
+        let a = Quat::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), 40.0);
+        let b = Quat::from_axis_angle(Vec3::new(0.0, 1.0, 1.0), -65.0);
+
+        assert_mat_eq(&(a * b).to_mat4(), &(a.to_mat4() * b.to_mat4()));
+        assert_mat_eq(&(a * a.conjugate()).to_mat4(), &Mat4::identity());
+    }
+
+    #[test]
+    fn slerp_interpolates_the_angle() {
+        let axis = Vec3::new(0.0, 1.0, 0.0);
+        let a = Quat::from_axis_angle(axis, 10.0);
+        let b = Quat::from_axis_angle(axis, 130.0);
+
+        assert_mat_eq(&Quat::slerp(&a, &b, 0.0).to_mat4(), &a.to_mat4());
+        assert_mat_eq(&Quat::slerp(&a, &b, 1.0).to_mat4(), &b.to_mat4());
+        assert_mat_eq(
+            &Quat::slerp(&a, &b, 0.25).to_mat4(),
+            &Quat::from_axis_angle(axis, 40.0).to_mat4(),
+        );
+    }
+
+    #[test]
+    fn slerp_takes_the_shorter_arc() {
+        let axis = Vec3::new(0.0, 0.0, 1.0);
+        let a = Quat::from_axis_angle(axis, 0.0);
+        // -q is the same 350° rotation, i.e. -10°.
+        let b = Quat::from_axis_angle(axis, 350.0);
+
+        assert_mat_eq(
+            &Quat::slerp(&a, &b, 0.5).to_mat4(),
+            &Quat::from_axis_angle(axis, -5.0).to_mat4(),
+        );
+    }
+}
@@ -0,0 +1,449 @@
+//! Minimal zlib (RFC 1950) stream support for the image encoders and decoders.
+
+use std::io;
+
+/// Largest payload a single stored deflate block can carry.
+const MAX_STORED_BLOCK: usize = 65535;
+
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    // 5552 is the largest n such that 255n(n+1)/2 + (n+1)(MOD_ADLER-1) fits in u32.
+    const NMAX: usize = 5552;
+
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+
+    for chunk in data.chunks(NMAX) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+
+    (b << 16) | a
+}
+
+/// Wraps `data` in a zlib stream made of uncompressed (stored) deflate blocks.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(MAX_STORED_BLOCK).max(1);
+    let mut out = Vec::with_capacity(data.len() + blocks * 5 + 6);
+
+    // CMF: deflate with a 32K window; FLG: no dictionary, check bits make it a multiple of 31.
+    out.extend_from_slice(&[0x78, 0x01]);
+
+    let mut chunks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if chunks.peek().is_none() {
+        // An empty input still needs one final (empty) block.
+        out.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(chunk) = chunks.next() {
+        let is_final = chunks.peek().is_none();
+        let len = chunk.len() as u16;
+
+        out.push(is_final as u8); // BFINAL bit, BTYPE = 00 (stored)
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid zlib stream: {}", message))
+}
+
+/// Reads a deflate stream bit by bit, least significant bit first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0, buffer: 0, count: 0 }
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        let byte = *self.data.get(self.pos).ok_or_else(|| invalid("unexpected end of data"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn bits(&mut self, n: u32) -> io::Result<u32> {
+        // Whole bytes are only loaded when needed, so at most 7 bits are ever left over.
+        while self.count < n {
+            self.buffer |= (self.byte()? as u32) << self.count;
+            self.count += 8;
+        }
+
+        let value = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Drops the rest of the current byte.
+    fn align_to_byte(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+const MAX_CODE_BITS: usize = 15;
+
+/// Canonical Huffman code, stored as the number of codes of each length and
+/// the symbols ordered by code.
+struct Huffman {
+    counts: [u16; MAX_CODE_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; MAX_CODE_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; MAX_CODE_BITS + 2];
+        for len in 1..=MAX_CODE_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec![0; offsets[MAX_CODE_BITS + 1] as usize];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> io::Result<u16> {
+        // `first` is the first code of the current length, `index` the
+        // position of its symbol.
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for len in 1..=MAX_CODE_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(invalid("bad Huffman code"))
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+/// Order in which the code length code lengths of a dynamic block are sent.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[i] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    // Literal/length and distance code lengths form one sequence, and runs may
+    // cross from one into the other.
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match code_length_code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or_else(|| invalid("repeat with no previous length"))?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if lengths.len() + repeat > literal_count + distance_count {
+            return Err(invalid("too many code lengths"));
+        }
+        lengths.extend(std::iter::repeat_n(value, repeat));
+    }
+
+    if lengths[256] == 0 {
+        return Err(invalid("no end-of-block code"));
+    }
+
+    Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])))
+}
+
+fn too_long() -> io::Error {
+    invalid("output is longer than expected")
+}
+
+fn inflate_block(
+        reader: &mut BitReader,
+        out: &mut Vec<u8>,
+        limit: usize,
+        literals: &Huffman,
+        distances: &Huffman
+    ) -> io::Result<()> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+
+        match symbol {
+            0..=255 => {
+                if out.len() >= limit {
+                    return Err(too_long());
+                }
+                out.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            _ => {
+                let i = symbol - 257;
+                if i >= LENGTH_BASE.len() {
+                    return Err(invalid("bad length code"));
+                }
+                let length = LENGTH_BASE[i] as usize + reader.bits(LENGTH_EXTRA[i] as u32)? as usize;
+
+                let d = distances.decode(reader)? as usize;
+                if d >= DISTANCE_BASE.len() {
+                    return Err(invalid("bad distance code"));
+                }
+                let distance = DISTANCE_BASE[d] as usize + reader.bits(DISTANCE_EXTRA[d] as u32)? as usize;
+                if distance > out.len() {
+                    return Err(invalid("distance too far back"));
+                }
+                if length > limit - out.len() {
+                    return Err(too_long());
+                }
+
+                // Copies may overlap their own output, so go byte by byte.
+                let start = out.len() - distance;
+                for k in 0..length {
+                    out.push(out[start + k]);
+                }
+            }
+        }
+    }
+}
+
+/// Decodes a zlib stream, checking its Adler-32 checksum. Fails as soon as
+/// the output would grow past `limit` bytes, so that a small stream cannot
+/// expand without bound.
+pub fn decompress(data: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+    if data.len() < 6 {
+        return Err(invalid("too short"));
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0F != 8 || !(cmf as u16 * 256 + flg as u16).is_multiple_of(31) {
+        return Err(invalid("bad header"));
+    }
+    if flg & 0x20 != 0 {
+        return Err(invalid("preset dictionaries are not supported"));
+    }
+
+    let mut reader = BitReader::new(&data[2..]);
+    let mut out = Vec::new();
+
+    loop {
+        let is_final = reader.bits(1)? == 1;
+
+        match reader.bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let len = u16::from_le_bytes([reader.byte()?, reader.byte()?]);
+                let nlen = u16::from_le_bytes([reader.byte()?, reader.byte()?]);
+                if len != !nlen {
+                    return Err(invalid("stored block length mismatch"));
+                }
+                if len as usize > limit - out.len() {
+                    return Err(too_long());
+                }
+                for _ in 0..len {
+                    out.push(reader.byte()?);
+                }
+            }
+            1 => {
+                let (literals, distances) = fixed_codes();
+                inflate_block(&mut reader, &mut out, limit, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut out, limit, &literals, &distances)?;
+            }
+            _ => return Err(invalid("reserved block type")),
+        }
+
+        if is_final {
+            break;
+        }
+    }
+
+    reader.align_to_byte();
+    let mut checksum = [0u8; 4];
+    for byte in &mut checksum {
+        *byte = reader.byte()?;
+    }
+    if u32::from_be_bytes(checksum) != adler32(&out) {
+        return Err(invalid("checksum mismatch"));
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Adler-32 with a modulo after every byte, as written in RFC 1950.
+    fn naive_adler32(data: &[u8]) -> u32 {
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in data {
+            a = (a + byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+
+        (b << 16) | a
+    }
+
+    #[test]
+    fn adler32_known_answers() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn adler32_defers_the_modulo_safely() {
+        // All 0xFF is the worst case for the deferred reduction.
+        let data = vec![0xFF; 3 * 5552 + 17];
+
+        assert_eq!(adler32(&data), naive_adler32(&data));
+    }
+
+    #[test]
+    fn compress_emits_stored_blocks() {
+        let out = compress(b"hello");
+
+        assert_eq!(&out[..2], &[0x78, 0x01]);
+        assert_eq!((out[0] as u16 * 256 + out[1] as u16) % 31, 0);
+        // Final stored block, LEN = 5, NLEN = !5.
+        assert_eq!(&out[2..7], &[0x01, 0x05, 0x00, 0xFA, 0xFF]);
+        assert_eq!(&out[7..12], b"hello");
+        assert_eq!(&out[12..], &adler32(b"hello").to_be_bytes());
+    }
+
+    #[test]
+    fn compress_empty_input() {
+        assert_eq!(compress(b""), [0x78, 0x01, 0x01, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x01]);
+    }
+
+    /// `zlib.compress(b"hello hello hello hello", 9)`: fixed Huffman codes.
+    const FIXED: [u8; 16] = [
+        0x78, 0xDA, 0xCB, 0x48, 0xCD, 0xC9, 0xC9, 0x57, 0xC8, 0x40, 0x27, 0x01, 0x68, 0x03, 0x08, 0xB1,
+    ];
+
+    /// `zlib.compress(dynamic_input(), 9)`: dynamic Huffman codes.
+    const DYNAMIC: [u8; 49] = [
+        0x78, 0xDA, 0xED, 0xCA, 0xB9, 0x01, 0x00, 0x20, 0x08, 0x03, 0xC0, 0x59, 0x79, 0x0D, 0x12, 0xF6,
+        0x6F, 0x9D, 0xC1, 0x9E, 0xAB, 0x4F, 0x60, 0x73, 0x01, 0x14, 0x35, 0x19, 0x84, 0xCB, 0x90, 0x62,
+        0xD9, 0x56, 0x4E, 0xA4, 0x9B, 0xC7, 0x69, 0x3D, 0xB2, 0x6D, 0xDB, 0x4F, 0x7B, 0x80, 0x1D, 0xF1,
+        0x75,
+    ];
+
+    fn dynamic_input() -> Vec<u8> {
+        (0..600usize).map(|i| ((i * i * 7 + i / 3) % 13 + 97) as u8).collect()
+    }
+
+    #[test]
+    fn decompress_inverts_compress() {
+        for len in [0, 1, 1000, MAX_STORED_BLOCK + 10] {
+            let data: Vec<u8> = (0..len).map(|i| (i * 31 % 251) as u8).collect();
+
+            assert_eq!(decompress(&compress(&data), len).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn decompress_huffman_streams() {
+        assert_eq!(decompress(&FIXED, 100).unwrap(), b"hello hello hello hello");
+        assert_eq!(decompress(&DYNAMIC, 600).unwrap(), dynamic_input());
+    }
+
+    #[test]
+    fn decompress_stops_at_the_limit() {
+        let error = |data: &[u8], limit| decompress(data, limit).unwrap_err().to_string();
+        let expected = "invalid zlib stream: output is longer than expected";
+
+        assert_eq!(error(&compress(&[7; 100]), 99), expected);
+        assert_eq!(error(&FIXED, 22), expected);
+        assert_eq!(error(&DYNAMIC, 599), expected);
+        assert_eq!(error(&DYNAMIC, 10), expected);
+    }
+
+    #[test]
+    fn decompress_rejects_damaged_streams() {
+        let mut bad_checksum = FIXED;
+        bad_checksum[15] ^= 1;
+        assert_eq!(
+            decompress(&bad_checksum, 100).unwrap_err().to_string(),
+            "invalid zlib stream: checksum mismatch"
+        );
+
+        for len in 0..DYNAMIC.len() {
+            assert!(decompress(&DYNAMIC[..len], 600).is_err(), "truncated to {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn compress_splits_long_input_into_blocks() {
+        let data: Vec<u8> = (0..MAX_STORED_BLOCK + 10).map(|i| i as u8).collect();
+        let out = compress(&data);
+
+        // A full non-final block, then a final one with the last 10 bytes.
+        assert_eq!(&out[2..7], &[0x00, 0xFF, 0xFF, 0x00, 0x00]);
+        let second = 2 + 5 + MAX_STORED_BLOCK;
+        assert_eq!(&out[second..second + 5], &[0x01, 0x0A, 0x00, 0xF5, 0xFF]);
+        assert_eq!(out.len(), 2 + 2 * 5 + data.len() + 4);
+    }
+}